@@ -1,4 +1,4 @@
-use crate::{point::Point, vector::Vector, ray::Ray};
+use crate::{point::Point, vector::Vector, ray::Ray, matrix::Matrix};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AABB {
@@ -42,6 +42,21 @@ impl AABB {
         self.min + (self.size() / 2.0)
     }
 
+    /// Surface area of the box, zero for an empty one.
+    pub fn surface_area(self) -> f32 {
+        let size = self.size();
+        if size.x < 0. || size.y < 0. || size.z < 0. {
+            0.
+        } else {
+            2. * (size.x * size.y + size.y * size.z + size.z * size.x)
+        }
+    }
+
+    /// Whether every bound is finite, which is false for empty and unbounded boxes.
+    pub fn is_finite(self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
     pub fn merge(self, other: AABB) -> AABB {
         AABB::with_bounds(
             Point::new(
@@ -57,6 +72,18 @@ impl AABB {
         )
     }
 
+    /// Smallest box around the transformed corners of this one.
+    pub fn apply_transform(self, transform: &Matrix) -> AABB {
+        if !self.is_finite() {
+            return self;
+        }
+        (0..8).fold(AABB::empty(), |aabb, corner| {
+            let pick = |axis: usize| if corner & (1 << axis) == 0 { self.min[axis] } else { self.max[axis] };
+            let point = transform.transform_point(Point::new(pick(0), pick(1), pick(2)));
+            aabb.merge(AABB::with_bounds(point, point))
+        })
+    }
+
     pub fn longest_axis(self) -> usize {
         let size = self.size();
         if size.x > size.y && size.x > size.z {
@@ -69,55 +96,214 @@ impl AABB {
     }
 
     pub fn intersect(self, ray: Ray) -> bool {
-        let mut tmin = (self.min.x - ray.origin.x) / ray.direction.x;
-        let mut tmax = (self.max.x - ray.origin.x) / ray.direction.x;
+        self.intersect_range(ray, ray.inverse_direction(), 0., f32::INFINITY).is_some()
+    }
 
-        if tmin > tmax {
-            std::mem::swap(&mut tmin, &mut tmax);
-        }
+    /// Distances at which the ray enters and leaves the box, clipped to
+    /// `[t_min, t_max]`, or `None` when it misses the box within that range.
+    /// `inverse_direction` is [`Ray::inverse_direction`], computed once per
+    /// ray. Rays parallel to a pair of faces hit when they run between or
+    /// along them, and the exit is widened by a few ulps so that rounding
+    /// never loses a grazing hit.
+    pub fn intersect_range(self, ray: Ray, inverse_direction: Vector, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let mut entry = t_min;
+        let mut exit = t_max;
 
-        let mut tymin = (self.min.y - ray.origin.y) / ray.direction.y;
-        let mut tymax = (self.max.y - ray.origin.y) / ray.direction.y;
+        for axis in 0..3 {
+            let origin = ray.origin[axis];
+            let inverse = inverse_direction[axis];
+            if inverse.is_infinite() {
+                // Parallel to the slab, where the products below could be 0 * inf.
+                if origin < self.min[axis] || origin > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
 
-        if tymin > tymax {
-            std::mem::swap(&mut tymin, &mut tymax);
+            let mut near = (self.min[axis] - origin) * inverse;
+            let mut far = (self.max[axis] - origin) * inverse;
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            entry = entry.max(near);
+            exit = exit.min(far + far.abs() * SLAB_ROUNDING);
+            if entry > exit {
+                return None;
+            }
         }
 
-        if tmin > tymax || tymin > tmax {
-            return false;
-        }
+        Some((entry, exit))
+    }
+}
 
-        if tymin > tmin {
-            tmin = tymin;
-        }
+/// Bound on the relative rounding error of a slab distance, three roundings
+/// of half an ulp each, doubled to cover the entry distance as well.
+const SLAB_ROUNDING: f32 = 2. * (3. * f32::EPSILON / 2.) / (1. - 3. * f32::EPSILON / 2.);
 
-        if tymax < tmax {
-            tmax = tymax;
-        }
+pub trait Bounded {
+    fn aabb(&self) -> AABB;
+}
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
 
-        let mut tzmin = (self.min.z - ray.origin.z) / ray.direction.z;
-        let mut tzmax = (self.max.z - ray.origin.z) / ray.direction.z;
+    fn unit_box() -> AABB {
+        AABB::with_bounds(Point::new(0., 0., 0.), Point::new(1., 1., 1.))
+    }
+
+    fn range(aabb: AABB, ray: Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        aabb.intersect_range(ray, ray.inverse_direction(), t_min, t_max)
+    }
 
-        if tzmin > tzmax {
-            std::mem::swap(&mut tzmin, &mut tzmax);
+    /// Compares ranges up to the slack added to exits.
+    fn assert_range(expected: Option<(f32, f32)>, actual: Option<(f32, f32)>) {
+        match (expected, actual) {
+            (Some(expected), Some(actual)) => assert!(
+                expected.0 == actual.0 && (expected.1 == actual.1 || (expected.1 - actual.1).abs() <= 1e-6 * expected.1.abs()),
+                "expected {:?}, got {:?}", expected, actual
+            ),
+            _ => assert_eq!(expected, actual)
         }
+    }
+
+    #[test]
+    fn test_intersect_range() {
+        let ray = Ray::new(Point::new(0.5, 0.5, -2.), Vector::new(0., 0., 1.));
+        assert_range(Some((2., 3.)), range(unit_box(), ray, 0., f32::INFINITY));
+        assert_range(Some((2.5, 3.)), range(unit_box(), ray, 2.5, 10.));
+        assert_range(Some((2., 2.5)), range(unit_box(), ray, 0., 2.5));
+        assert_eq!(None, range(unit_box(), ray, 0., 1.5));
+        assert_eq!(None, range(unit_box(), ray, 3.5, 10.));
+
+        // Starting inside enters at the start of the range.
+        let inside = Ray::new(Point::new(0.5, 0.5, 0.5), Vector::new(1., 1., 0.));
+        let (entry, exit) = range(unit_box(), inside, 0., f32::INFINITY).unwrap();
+        assert_eq!(0., entry);
+        assert!((exit - 0.5 * 2f32.sqrt()).abs() < 1e-6);
 
-        if tmin > tzmax || tzmin > tmax {
-            return false;
+        // Pointing away misses.
+        assert!(!unit_box().intersect(Ray::new(Point::new(0.5, 0.5, -2.), Vector::new(0., 0., -1.))));
+    }
+
+    #[test]
+    fn test_axis_aligned_on_boundary() {
+        // Zero direction components with the origin on a face used to give 0 * inf.
+        for origin in [Point::new(0., 0.5, -1.), Point::new(1., 1., -1.), Point::new(0., 0., -1.)] {
+            assert_range(Some((1., 2.)), range(unit_box(), Ray::new(origin, Vector::new(0., 0., 1.)), 0., f32::INFINITY));
         }
+        let negative_zero = Ray { origin: Point::new(1., 0.5, 3.), direction: Vector::new(-0., 0., -1.) };
+        assert_range(Some((2., 3.)), range(unit_box(), negative_zero, 0., f32::INFINITY));
+        assert_eq!(None, range(unit_box(), Ray::new(Point::new(1.001, 0.5, -1.), Vector::new(0., 0., 1.)), 0., f32::INFINITY));
+
+        // Flat boxes, like the bounds of an axis-aligned triangle.
+        let flat = AABB::with_bounds(Point::new(0., 0., 1.), Point::new(1., 1., 1.));
+        assert_range(Some((2., 2.)), range(flat, Ray::new(Point::new(0.5, 0.5, 3.), Vector::new(0., 0., -1.)), 0., f32::INFINITY));
+    }
+
+    #[test]
+    fn test_full() {
+        let ray = Ray::new(Point::new(3., 4., 5.), Vector::new(0., 1., 0.));
+        assert_range(Some((0., f32::INFINITY)), range(AABB::full(), ray, 0., f32::INFINITY));
+        assert_eq!(None, range(AABB::empty(), ray, 0., f32::INFINITY));
+    }
+
+    #[test]
+    fn test_surface_area() {
+        assert_eq!(22., AABB::with_bounds(Point::new(0., 0., 0.), Point::new(1., 2., 3.)).surface_area());
+        assert_eq!(0., AABB::empty().surface_area());
+    }
+
+    #[test]
+    fn test_apply_transform() {
+        let aabb = unit_box().apply_transform(&Matrix::translate(1., 0., 0.).multiply(&Matrix::rotate_z(std::f32::consts::FRAC_PI_4)));
+        let half_diagonal = 0.5 * 2f32.sqrt();
+        assert!((aabb.min - Point::new(1. - half_diagonal, 0., 0.)).len() < 1e-6);
+        assert!((aabb.max - Point::new(1. + half_diagonal, 2. * half_diagonal, 1.)).len() < 1e-6);
+        assert_eq!(AABB::full(), AABB::full().apply_transform(&Matrix::scale(2., 2., 2.)));
+    }
+
+    /// Coordinates that often coincide exactly with each other.
+    fn coordinate() -> impl Strategy<Value = f32> {
+        prop_oneof![-10f32..10., (-3i32..=3).prop_map(|value| value as f32)]
+    }
+
+    fn point() -> impl Strategy<Value = Point> {
+        (coordinate(), coordinate(), coordinate()).prop_map(|(x, y, z)| Point::new(x, y, z))
+    }
 
-        if tzmin > tmin {
-            tmin = tzmin;
+    fn aabb() -> impl Strategy<Value = AABB> {
+        (point(), point()).prop_map(|(a, b)| AABB::with_bounds(
+            Point::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            Point::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+        ))
+    }
+
+    /// Directions with components that are often exactly zero, of either sign.
+    fn direction() -> impl Strategy<Value = Vector> {
+        let component = prop_oneof![-1f32..1., Just(0.), Just(-0.)];
+        (component.clone(), component.clone(), component)
+            .prop_map(|(x, y, z)| Vector::new(x, y, z))
+            .prop_filter("direction must not vanish", |direction| direction.len() > 1e-3)
+    }
+
+    fn ray() -> impl Strategy<Value = Ray> {
+        (point(), direction()).prop_map(|(origin, direction)| Ray::new(origin, direction))
+    }
+
+    fn interval() -> impl Strategy<Value = (f32, f32)> {
+        (0f32..20., prop_oneof![0f32..20., Just(f32::INFINITY)]).prop_map(|(a, b)| (a.min(b), a.max(b)))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_range_lies_in_box_and_interval(aabb in aabb(), ray in ray(), (t_min, t_max) in interval()) {
+            if let Some((entry, exit)) = range(aabb, ray, t_min, t_max) {
+                prop_assert!(!entry.is_nan() && !exit.is_nan());
+                prop_assert!(t_min <= entry && entry <= exit && exit <= t_max);
+                let middle = ray.at((entry + exit) / 2.);
+                let tolerance = 1e-4 * (1. + (entry + exit).abs());
+                for axis in 0..3 {
+                    prop_assert!(middle[axis] >= aabb.min[axis] - tolerance && middle[axis] <= aabb.max[axis] + tolerance);
+                }
+            }
         }
 
-        if tzmax < tmax {
-            tmax = tzmax;
+        #[test]
+        fn prop_hits_points_in_box(
+            aabb in aabb(),
+            fractions in (prop_oneof![0f32..=1., Just(0.), Just(1.)], prop_oneof![0f32..=1., Just(0.), Just(1.)], prop_oneof![0f32..=1., Just(0.), Just(1.)]),
+            origin in point(),
+            aligned in (any::<bool>(), any::<bool>(), any::<bool>())
+        ) {
+            let lerp = |axis: usize, fraction: f32| (aabb.min[axis] + (aabb.max[axis] - aabb.min[axis]) * fraction).clamp(aabb.min[axis], aabb.max[axis]);
+            let target = Point::new(lerp(0, fractions.0), lerp(1, fractions.1), lerp(2, fractions.2));
+            // Sharing coordinates with the target makes those direction
+            // components exactly zero, but at most two of them.
+            let aligned = (aligned.0 && !(aligned.1 && aligned.2), aligned.1, aligned.2);
+            let origin = Point::new(
+                if aligned.0 { target.x } else { origin.x },
+                if aligned.1 { target.y } else { origin.y },
+                if aligned.2 { target.z } else { origin.z }
+            );
+            let distance = (target - origin).len();
+            prop_assume!(distance > 1e-3);
+
+            let ray = Ray::new(origin, target - origin);
+            let hit = range(aabb, ray, 0., f32::INFINITY);
+            prop_assert!(hit.is_some(), "{:?} misses {:?} inside {:?}", ray, target, aabb);
+            let (entry, exit) = hit.unwrap();
+            let tolerance = 1e-4 * (1. + distance);
+            prop_assert!(entry <= distance + tolerance && distance <= exit + tolerance);
         }
 
-        tmin >= 0.0 && tmax >= 0.0
+        #[test]
+        fn prop_range_clips_full_range(aabb in aabb(), ray in ray(), (t_min, t_max) in interval()) {
+            let clipped = range(aabb, ray, 0., f32::INFINITY)
+                .map(|(entry, exit)| (entry.max(t_min), exit.min(t_max)))
+                .filter(|(entry, exit)| entry <= exit);
+            prop_assert_eq!(clipped, range(aabb, ray, t_min, t_max));
+        }
     }
 }
-
-pub trait Bounded {
-    fn aabb(&self) -> AABB;
-}
\ No newline at end of file
@@ -1,107 +1,511 @@
-use crate::{aabb::{AABB, Bounded}, intersectable::Intersectable, ray::Ray, intersection::Intersection};
+use std::{collections::HashMap, sync::Arc};
 
-enum Node {
-    Leaf(Vec<Box<Intersectable>>),
-    Branch(AABB, Box<Node>, Box<Node>)
+use crate::{aabb::{AABB, Bounded}, instance::Instance, intersectable::Intersectable, mesh::Mesh, point::Point, ray::Ray, intersection::Intersection};
+
+/// Number of buckets primitive centroids are sorted into when looking for a split.
+const BINS: usize = 12;
+
+/// Deepest a tree is allowed to grow, which bounds the traversal stack.
+const MAX_DEPTH: usize = 64;
+
+/// What a leaf refers to: an object of the tree, a face of one of its
+/// meshes, or an instance, so mesh triangles are never copied into the tree.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Primitive {
+    Object(u32),
+    Face { mesh: u32, face: u32 },
+    Instance(u32)
 }
 
-impl Node {
-    fn intersect(&self, ray: Ray) -> Option<Intersection> {
-        match self {
-            Node::Leaf(objects) => {
-                let mut intersections = Vec::new();
-                for object in objects {
-                    if let Some(intersection) = object.intersect(ray) {
-                        intersections.push(intersection);
-                    }
-                }
-                if intersections.is_empty() {
-                    None
-                } else {
-                    intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
-                    Some(intersections[0])
-                }
-            },
-            Node::Branch(aabb, left, right) => {
-                if !aabb.intersect(ray) {
-                    None
-                } else {
-                    let left_intersection = left.intersect(ray);
-                    let right_intersection = right.intersect(ray);
-                    if let Some(left_intersection) = left_intersection {
-                        if let Some(right_intersection) = right_intersection {
-                            if left_intersection.t < right_intersection.t {
-                                Some(left_intersection)
-                            } else {
-                                Some(right_intersection)
-                            }
-                        } else {
-                            Some(left_intersection)
-                        }
-                    } else {
-                        right_intersection
-                    }
-                }
+struct Primitives {
+    objects: Vec<Intersectable>,
+    meshes: Vec<Arc<Mesh>>,
+    instances: Vec<Instance>,
+    /// Trees over the faces of the instanced meshes, one per distinct mesh.
+    blases: Vec<BVH>,
+    /// Index into `blases` of every instance.
+    instance_blases: Vec<u32>
+}
+
+impl Primitives {
+    fn aabb(&self, primitive: Primitive) -> AABB {
+        match primitive {
+            Primitive::Object(object) => self.objects[object as usize].aabb(),
+            Primitive::Face { mesh, face } => self.meshes[mesh as usize].face_aabb(face as usize),
+            Primitive::Instance(instance) => {
+                let blas = &self.blases[self.instance_blases[instance as usize] as usize];
+                blas.bounds().apply_transform(self.instances[instance as usize].transform())
             }
         }
     }
+
+    /// Hit with `primitive` closer than `t_max`, which instances also use to
+    /// prune their own tree.
+    fn intersect(&self, primitive: Primitive, ray: Ray, t_max: f32, any_hit: bool) -> Option<Intersection> {
+        let intersection = match primitive {
+            Primitive::Object(object) => self.objects[object as usize].intersect(ray),
+            Primitive::Face { mesh, face } => self.meshes[mesh as usize].intersect(face as usize, ray),
+            Primitive::Instance(instance) => {
+                let blas = &self.blases[self.instance_blases[instance as usize] as usize];
+                let instance = &self.instances[instance as usize];
+                blas.traverse(instance.object_ray(ray), t_max, any_hit).map(|intersection| instance.world_intersection(intersection))
+            }
+        };
+        intersection.filter(|intersection| intersection.t < t_max)
+    }
+}
+
+/// A node of the flattened tree. The first child of a branch directly
+/// follows it in the node array, so only the second one is stored.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    aabb: AABB,
+    /// First primitive of a leaf, or the second child of a branch.
+    offset: u32,
+    /// Number of primitives in a leaf, zero for branches.
+    count: u32,
+    /// Axis a branch was split along, to visit the nearer child first.
+    axis: u8
+}
+
+impl Node {
+    fn leaf(aabb: AABB, offset: usize, count: usize) -> Node {
+        Node { aabb, offset: offset as u32, count: count as u32, axis: 0 }
+    }
+
+    fn branch(aabb: AABB, second: usize, axis: usize) -> Node {
+        Node { aabb, offset: second as u32, count: 0, axis: axis as u8 }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// A primitive with the bounds the builder sorts it by.
+#[derive(Clone, Copy)]
+struct Item {
+    primitive: Primitive,
+    aabb: AABB,
+    centroid: Point
 }
 
+#[derive(Clone, Copy)]
+struct Bin {
+    aabb: AABB,
+    count: usize
+}
+
+/// Bounding volume hierarchy over the objects, mesh faces and instances of a
+/// scene, split by the surface area heuristic and stored as a flat node array.
+/// Instances are leaves of this top level tree that continue into a bottom
+/// level tree over their mesh, built once however often the mesh is placed.
+/// Primitives without finite bounds, like infinite planes, cannot be placed
+/// in it and are tested against every ray instead.
 pub struct BVH {
-    root: Box<Node>
+    nodes: Vec<Node>,
+    order: Vec<Primitive>,
+    unbounded: Vec<Primitive>,
+    primitives: Primitives
 }
 
 impl BVH {
-    pub fn new(objects: Vec<Box<Intersectable>>, depth: u32, max_depth: u32) -> BVH {
+    /// Leaf size used by [`BVH::new`].
+    pub const DEFAULT_LEAF_SIZE: usize = 4;
+
+    pub fn new(objects: Vec<Intersectable>, meshes: Vec<Arc<Mesh>>, instances: Vec<Instance>) -> BVH {
+        BVH::with_leaf_size(objects, meshes, instances, BVH::DEFAULT_LEAF_SIZE)
+    }
+
+    /// Builds a tree whose leaves hold at most `leaf_size` primitives, unless
+    /// more share a centroid at the depth limit.
+    pub fn with_leaf_size(objects: Vec<Intersectable>, meshes: Vec<Arc<Mesh>>, instances: Vec<Instance>, leaf_size: usize) -> BVH {
+        let leaf_size = leaf_size.max(1);
+        let mut primitives: Vec<Primitive> = (0..objects.len() as u32).map(Primitive::Object).collect();
+        for (mesh_index, mesh) in meshes.iter().enumerate() {
+            primitives.extend((0..mesh.faces.len() as u32).map(|face| Primitive::Face { mesh: mesh_index as u32, face }));
+        }
+        primitives.extend((0..instances.len() as u32).map(Primitive::Instance));
+
+        let mut blas_meshes: Vec<Arc<Mesh>> = Vec::new();
+        let mut blas_indices = HashMap::new();
+        let instance_blases = instances
+            .iter()
+            .map(|instance| {
+                *blas_indices.entry(Arc::as_ptr(&instance.mesh)).or_insert_with(|| {
+                    blas_meshes.push(instance.mesh.clone());
+                    blas_meshes.len() as u32 - 1
+                })
+            })
+            .collect();
+        let blases = blas_meshes
+            .into_iter()
+            .map(|mesh| BVH::with_leaf_size(Vec::new(), vec![mesh], Vec::new(), leaf_size))
+            .collect();
+        let store = Primitives { objects, meshes, instances, blases, instance_blases };
+
+        let (mut items, unbounded): (Vec<Item>, Vec<Item>) = primitives
+            .into_iter()
+            .map(|primitive| {
+                let aabb = store.aabb(primitive);
+                Item { primitive, aabb, centroid: aabb.center() }
+            })
+            .partition(|item| item.aabb.is_finite());
+
+        let nodes = if items.is_empty() {
+            Vec::new()
+        } else {
+            BVH::build(&mut items, leaf_size, 0)
+        };
+
         BVH {
-            root: Box::new(BVH::build_tree(objects, depth, max_depth))
+            nodes,
+            order: items.iter().map(|item| item.primitive).collect(),
+            unbounded: unbounded.iter().map(|item| item.primitive).collect(),
+            primitives: store
         }
     }
 
-    fn build_tree(objects: Vec<Box<Intersectable>>, depth: u32, max_depth: u32) -> Node {
-        if depth >= max_depth || objects.len() <= 100_000 {
-            Node::Leaf(objects)
-        } else {
-            let mut aabb = AABB::empty();
-            for object in &objects {
-                if object.aabb() != AABB::full() {
-                    aabb = aabb.merge(object.aabb());
+    /// Builds the subtree over `items`, reordering them so that every leaf
+    /// covers a contiguous range. Offsets are relative to the subtree.
+    fn build(items: &mut [Item], leaf_size: usize, depth: usize) -> Vec<Node> {
+        let aabb = items.iter().fold(AABB::empty(), |aabb, item| aabb.merge(item.aabb));
+        if items.len() <= leaf_size || depth + 1 >= MAX_DEPTH {
+            return vec![Node::leaf(aabb, 0, items.len())];
+        }
+
+        let (axis, middle) = BVH::split(items);
+        let (left, right) = items.split_at_mut(middle);
+        let (left_nodes, right_nodes) = rayon::join(
+            || BVH::build(left, leaf_size, depth + 1),
+            || BVH::build(right, leaf_size, depth + 1)
+        );
+
+        let mut nodes = Vec::with_capacity(1 + left_nodes.len() + right_nodes.len());
+        nodes.push(Node::branch(aabb, 1 + left_nodes.len(), axis));
+        let second = nodes[0].offset as usize;
+        nodes.extend(BVH::shifted(left_nodes, 1, 0));
+        nodes.extend(BVH::shifted(right_nodes, second, middle));
+        nodes
+    }
+
+    /// Partitions `items` at the cheapest of the binned surface area
+    /// heuristic splits along the longest centroid axis, returning the axis
+    /// and the size of the first half.
+    fn split(items: &mut [Item]) -> (usize, usize) {
+        let centroids = items.iter().fold(AABB::empty(), |aabb, item| aabb.merge(AABB::with_bounds(item.centroid, item.centroid)));
+        let axis = centroids.longest_axis();
+        let min = centroids.min[axis];
+        let extent = centroids.max[axis] - min;
+
+        if extent <= 0. {
+            // Nothing to tell the centroids apart by, so halve the range.
+            return (axis, items.len() / 2);
+        }
+
+        let bin_of = |item: &Item| (((item.centroid[axis] - min) / extent * BINS as f32) as usize).min(BINS - 1);
+        let mut bins = [Bin { aabb: AABB::empty(), count: 0 }; BINS];
+        for item in items.iter() {
+            let bin = &mut bins[bin_of(item)];
+            bin.aabb = bin.aabb.merge(item.aabb);
+            bin.count += 1;
+        }
+
+        // Cost of splitting after each bin, up to a constant factor: the
+        // area of either side weighted by the primitives in it.
+        let mut costs = [0f32; BINS - 1];
+        let mut below = Bin { aabb: AABB::empty(), count: 0 };
+        for (cost, bin) in costs.iter_mut().zip(&bins) {
+            below = Bin { aabb: below.aabb.merge(bin.aabb), count: below.count + bin.count };
+            *cost = below.aabb.surface_area() * below.count as f32;
+        }
+        let mut above = Bin { aabb: AABB::empty(), count: 0 };
+        for (cost, bin) in costs.iter_mut().zip(&bins[1..]).rev() {
+            above = Bin { aabb: above.aabb.merge(bin.aabb), count: above.count + bin.count };
+            *cost += above.aabb.surface_area() * above.count as f32;
+        }
+
+        let best = (0..BINS - 1)
+            .filter(|&split| {
+                let count: usize = bins[..=split].iter().map(|bin| bin.count).sum();
+                count > 0 && count < items.len()
+            })
+            .min_by(|&a, &b| costs[a].total_cmp(&costs[b]))
+            .expect("centroids spanning an extent fall into at least two bins");
+
+        let mut middle = 0;
+        for i in 0..items.len() {
+            if bin_of(&items[i]) <= best {
+                items.swap(i, middle);
+                middle += 1;
+            }
+        }
+        (axis, middle)
+    }
+
+    /// Moves a subtree to start at node `nodes` and primitive `primitives`.
+    fn shifted(mut subtree: Vec<Node>, nodes: usize, primitives: usize) -> Vec<Node> {
+        for node in &mut subtree {
+            node.offset += if node.is_leaf() { primitives } else { nodes } as u32;
+        }
+        subtree
+    }
+
+    /// Bounds of everything in the tree that has finite bounds.
+    fn bounds(&self) -> AABB {
+        self.nodes.first().map_or(AABB::empty(), |root| root.aabb)
+    }
+
+    /// Closest hit along the ray, visiting nearer children first.
+    pub fn intersect(&self, ray: Ray) -> Option<Intersection> {
+        self.traverse(ray, f32::INFINITY, false)
+    }
+
+    /// Whether anything is hit closer than `t_max`, stopping at the first hit
+    /// found rather than the closest one.
+    pub fn occluded(&self, ray: Ray, t_max: f32) -> bool {
+        self.traverse(ray, t_max, true).is_some()
+    }
+
+    /// Tests the unbounded primitives, then walks the tree front to back,
+    /// skipping nodes that start beyond the closest hit so far. With
+    /// `any_hit` the first hit closer than `t_max` is returned as soon as it
+    /// is found.
+    fn traverse(&self, ray: Ray, t_max: f32, any_hit: bool) -> Option<Intersection> {
+        let mut closest: Option<Intersection> = None;
+        let mut t_max = t_max;
+        for &primitive in &self.unbounded {
+            if let Some(intersection) = self.primitives.intersect(primitive, ray, t_max, any_hit) {
+                if any_hit {
+                    return Some(intersection);
                 }
+                t_max = intersection.t;
+                closest = Some(intersection);
             }
-            let axis = aabb.longest_axis();
-
-            let mut left = Vec::new();
-            let mut right = Vec::new();
-
-            let split_point = match axis {
-                0 => aabb.min.x + (aabb.max.x - aabb.min.x) / 2.,
-                1 => aabb.min.y + (aabb.max.y - aabb.min.y) / 2.,
-                2 => aabb.min.z + (aabb.max.z - aabb.min.z) / 2.,
-                _ => panic!("invalid axis")
-            };
-
-            for object in objects {
-                if object.aabb() == AABB::full() {
-                    left.push(object.clone());
-                    right.push(object.clone());
+        }
+
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let inverse_direction = ray.inverse_direction();
+        let mut stack = [0u32; MAX_DEPTH];
+        let mut size = 0;
+        let mut index = 0;
+
+        loop {
+            let node = self.nodes[index];
+            if node.aabb.intersect_range(ray, inverse_direction, 0., t_max).is_some() {
+                if node.is_leaf() {
+                    let start = node.offset as usize;
+                    for &primitive in &self.order[start..start + node.count as usize] {
+                        if let Some(intersection) = self.primitives.intersect(primitive, ray, t_max, any_hit) {
+                            if any_hit {
+                                return Some(intersection);
+                            }
+                            t_max = intersection.t;
+                            closest = Some(intersection);
+                        }
+                    }
+                } else {
+                    let (near, far) = if ray.direction[node.axis as usize] < 0. {
+                        (node.offset as usize, index + 1)
+                    } else {
+                        (index + 1, node.offset as usize)
+                    };
+                    stack[size] = far as u32;
+                    size += 1;
+                    index = near;
                     continue;
                 }
-                if object.aabb().min[axis] <= split_point {
-                    left.push(object);
-                } else {
-                    right.push(object);
+            }
+
+            if size == 0 {
+                break;
+            }
+            size -= 1;
+            index = stack[size] as usize;
+        }
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use crate::{sphere::Sphere, plane::Plane, vector::Vector, triangle::Triangle, matrix::Matrix};
+
+    use super::*;
+
+    fn random_spheres(count: usize) -> Vec<Intersectable> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..count)
+            .map(|_| {
+                let center = Point::new(rng.gen_range(-10.0..10.), rng.gen_range(-10.0..10.), rng.gen_range(-10.0..10.));
+                Sphere::new(center, rng.gen_range(0.05..0.5)).into()
+            })
+            .collect()
+    }
+
+    fn random_rays(count: usize) -> Vec<Ray> {
+        let mut rng = StdRng::seed_from_u64(11);
+        (0..count)
+            .map(|_| {
+                let origin = Point::new(rng.gen_range(-15.0..15.), rng.gen_range(-15.0..15.), rng.gen_range(-15.0..15.));
+                let direction = Vector::new(rng.gen_range(-1.0..1.), rng.gen_range(-1.0..1.), rng.gen_range(-1.0..1.)).normalize();
+                Ray::new(origin, direction)
+            })
+            .collect()
+    }
+
+    fn brute_force(objects: &[Intersectable], ray: Ray) -> Option<Intersection> {
+        objects
+            .iter()
+            .filter_map(|object| object.intersect(ray))
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+
+    fn check_structure(tree: &BVH, leaf_size: usize) {
+        let mut seen = vec![false; tree.order.len()];
+        for (index, node) in tree.nodes.iter().enumerate() {
+            if node.is_leaf() {
+                assert!(node.count as usize <= leaf_size);
+                for primitive in node.offset..node.offset + node.count {
+                    assert!(!seen[primitive as usize]);
+                    seen[primitive as usize] = true;
+                    assert!(node.aabb.merge(tree.primitives.aabb(tree.order[primitive as usize])) == node.aabb);
                 }
+            } else {
+                let (first, second) = (tree.nodes[index + 1], tree.nodes[node.offset as usize]);
+                assert!(node.offset as usize > index + 1);
+                assert_eq!(node.aabb, first.aabb.merge(second.aabb));
             }
+        }
+        assert!(seen.into_iter().all(|seen| seen));
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let objects = random_spheres(500);
+        let tree = BVH::new(objects.clone(), Vec::new(), Vec::new());
+        check_structure(&tree, BVH::DEFAULT_LEAF_SIZE);
+        assert!(tree.nodes.len() > 100);
 
-            let (left_tree, right_tree) = rayon::join(
-                || BVH::build_tree(left, depth + 1, max_depth),
-                || BVH::build_tree(right, depth + 1, max_depth)
-            );
-            Node::Branch(aabb, Box::new(left_tree), Box::new(right_tree))
+        for ray in random_rays(500) {
+            let expected = brute_force(&objects, ray);
+            let actual = tree.intersect(ray);
+            assert_eq!(expected.map(|hit| hit.object), actual.map(|hit| hit.object));
         }
     }
 
-    pub fn intersect(&self, ray: Ray) -> Option<Intersection> {
-        self.root.intersect(ray)
+    #[test]
+    fn test_leaf_size() {
+        let objects = random_spheres(200);
+        let tree = BVH::with_leaf_size(objects.clone(), Vec::new(), Vec::new(), 16);
+        check_structure(&tree, 16);
+        assert_eq!(1, BVH::with_leaf_size(objects, Vec::new(), Vec::new(), 200).nodes.len());
+    }
+
+    #[test]
+    fn test_mesh_faces() {
+        let a = Triangle::new(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.));
+        let b = Triangle::new(Point::new(0., 0., -2.), Point::new(1., 0., -2.), Point::new(0., 1., -2.));
+        let mesh = Arc::new(Mesh::from_triangles(&[a, b]));
+        let tree = BVH::with_leaf_size(Vec::new(), vec![mesh], Vec::new(), 1);
+        assert_eq!(3, tree.nodes.len());
+
+        let down = tree.intersect(Ray::new(Point::new(0.2, 0.2, 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert!((down.t - 1.).abs() < 1e-6);
+        let up = tree.intersect(Ray::new(Point::new(0.2, 0.2, -3.), Vector::new(0., 0., 1.))).unwrap();
+        assert!((up.t - 1.).abs() < 1e-6);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_coincident_centroids() {
+        let objects = vec![Sphere::new(Point::new(0., 0., 0.), 1.).into(); 10];
+        let tree = BVH::with_leaf_size(objects, Vec::new(), Vec::new(), 2);
+        check_structure(&tree, 2);
+        assert!(tree.intersect(Ray::new(Point::new(0., 0., 5.), Vector::new(0., 0., -1.))).is_some());
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let mut objects = random_spheres(50);
+        objects.push(Plane::new(Vector::new(0., 1., 0.), Point::new(0., -20., 0.)).into());
+        objects.push(Plane::new(Vector::new(0., 0., 1.), Point::new(0., 0., 0.)).with_disc(1.).into());
+        let tree = BVH::new(objects.clone(), Vec::new(), Vec::new());
+        check_structure(&tree, BVH::DEFAULT_LEAF_SIZE);
+        assert_eq!(1, tree.unbounded.len());
+        assert_eq!(51, tree.order.len());
+
+        let down = Ray::new(Point::new(100., 0., 100.), Vector::new(0., -1., 0.));
+        assert!((tree.intersect(down).unwrap().t - 20.).abs() < 1e-4);
+        for ray in random_rays(200) {
+            assert_eq!(brute_force(&objects, ray).map(|hit| hit.object), tree.intersect(ray).map(|hit| hit.object));
+        }
+
+        assert!(BVH::new(Vec::new(), Vec::new(), Vec::new()).intersect(down).is_none());
+        let plane_only = BVH::new(vec![Plane::new(Vector::new(0., 1., 0.), Point::new(0., 0., 0.)).into()], Vec::new(), Vec::new());
+        assert!(plane_only.intersect(down).is_some());
+    }
+
+    #[test]
+    fn test_occluded() {
+        let objects = random_spheres(300);
+        let tree = BVH::new(objects.clone(), Vec::new(), Vec::new());
+
+        for ray in random_rays(300) {
+            let closest = brute_force(&objects, ray);
+            for t_max in [0.5, 5., f32::INFINITY] {
+                assert_eq!(closest.is_some_and(|hit| hit.t < t_max), tree.occluded(ray, t_max));
+            }
+        }
+
+        let blocker = Sphere::new(Point::new(0., 0., -5.), 1.).into();
+        let tree = BVH::new(vec![blocker], Vec::new(), Vec::new());
+        let ray = Ray::new(Point::new(0., 0., 0.), Vector::new(0., 0., -1.));
+        assert!(tree.occluded(ray, 10.));
+        assert!(!tree.occluded(ray, 3.));
+    }
+
+    #[test]
+    fn test_instances() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mesh = Arc::new(Mesh::from_triangles(&[
+            Triangle::new(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.)),
+            Triangle::new(Point::new(0., 0., 0.), Point::new(0., 1., 0.), Point::new(0., 0., 1.)),
+            Triangle::new(Point::new(0., 0., 0.), Point::new(0., 0., 1.), Point::new(1., 0., 0.)),
+            Triangle::new(Point::new(1., 0., 0.), Point::new(0., 1., 0.), Point::new(0., 0., 1.))
+        ]));
+        let instances: Vec<Instance> = (0..40)
+            .map(|_| {
+                let transform = Matrix::translate(rng.gen_range(-10.0..10.), rng.gen_range(-10.0..10.), rng.gen_range(-10.0..10.))
+                    .multiply(&Matrix::rotate_y(rng.gen_range(0.0..6.)))
+                    .multiply(&Matrix::scale(rng.gen_range(0.5..3.), rng.gen_range(0.5..3.), rng.gen_range(0.5..3.)));
+                Instance::new(mesh.clone(), transform)
+            })
+            .collect();
+        let objects = random_spheres(20);
+        let tree = BVH::new(objects.clone(), Vec::new(), instances.clone());
+        assert_eq!(1, tree.primitives.blases.len());
+        check_structure(&tree, BVH::DEFAULT_LEAF_SIZE);
+
+        for ray in random_rays(500) {
+            let expected = instances
+                .iter()
+                .flat_map(|instance| (0..mesh.faces.len()).filter_map(move |face| instance.intersect(face, ray)))
+                .chain(brute_force(&objects, ray))
+                .min_by(|a, b| a.t.total_cmp(&b.t));
+            let actual = tree.intersect(ray);
+            assert_eq!(expected.is_some(), actual.is_some());
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                assert!((expected.t - actual.t).abs() < 1e-4);
+                assert_eq!(expected.object, actual.object);
+                assert!(tree.occluded(ray, actual.t + 1e-3));
+                assert!(!tree.occluded(ray, actual.t - 1e-3));
+            }
+        }
+    }
+}
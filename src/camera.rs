@@ -1,44 +1,357 @@
-use crate::{point::Point, vector::Vector, ray::Ray};
+use rand::Rng;
+
+use crate::{point::Point, vector::Vector, ray::Ray, impl_froms, EPSILON};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Camera {
+pub enum Camera {
+    Perspective(Perspective),
+    Orthographic(Orthographic),
+    Fisheye(Fisheye),
+    Equirectangular(Equirectangular)
+}
+
+impl Camera {
+    pub fn ray_for_pixel(self, x: u32, y: u32) -> Ray {
+        self.ray_for_sample(x as f32 + 0.5, y as f32 + 0.5)
+    }
+
+    /// Ray through a continuous position on the film, measured in pixels
+    /// from the lower left corner.
+    pub fn ray_for_sample(self, x: f32, y: f32) -> Ray {
+        match self {
+            Camera::Perspective(camera) => camera.ray_for_sample(x, y),
+            Camera::Orthographic(camera) => camera.ray_for_sample(x, y),
+            Camera::Fisheye(camera) => camera.ray_for_sample(x, y),
+            Camera::Equirectangular(camera) => camera.ray_for_sample(x, y)
+        }
+    }
+}
+
+/// Orthonormal view frame shared by every projection. The camera looks along
+/// `-w`, with `u` pointing right and `v` up in the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Basis {
+    origin: Point,
+    u: Vector,
+    v: Vector,
+    w: Vector
+}
+
+impl Basis {
+    /// If `up` is parallel to the view direction any perpendicular vector
+    /// is used instead, since the image's vertical is undefined.
+    fn look_at(eye: Point, target: Point, up: Vector, roll: f32) -> Basis {
+        let w = (eye - target).normalize();
+        let u = match up.cross(w) {
+            u if u.len() > EPSILON => u.normalize(),
+            _ if w.x.abs() > 0.9 => Vector::new(0., 1., 0.).cross(w).normalize(),
+            _ => Vector::new(1., 0., 0.).cross(w).normalize()
+        };
+        let v = w.cross(u);
+
+        let roll = roll * std::f32::consts::PI / 180.0;
+        let (u, v) = (u * roll.cos() + v * roll.sin(), v * roll.cos() - u * roll.sin());
+
+        Basis { origin: eye, u, v, w }
+    }
+}
+
+fn default_basis(origin: Point) -> Basis {
+    Basis::look_at(origin, origin - Vector::new(0., 0., 1.), Vector::new(0., 1., 0.), 0.)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Perspective {
     pub origin: Point,
     lower_left_corner: Point,
     horizontal: Vector,
     pub vertical: Vector,
+    basis: Basis,
+    /// Radius of the lens; zero gives a pinhole camera with everything in focus.
+    pub aperture: f32,
+    /// Distance along the view direction of the plane that is in perfect focus.
+    pub focus_distance: f32,
     width: u32,
     height: u32,
 }
 
-impl Camera {
+impl Perspective {
     pub fn new(origin: Point, vfov: f32, aspect: f32, height: u32) -> Self {
+        Perspective::with_basis(default_basis(origin), vfov, aspect, height)
+    }
+
+    /// Camera at `eye` looking towards `target`, with `up` fixing the vertical
+    /// direction of the image and `roll` (in degrees) turning it around the
+    /// view axis.
+    pub fn look_at(eye: Point, target: Point, up: Vector, roll: f32, vfov: f32, aspect: f32, height: u32) -> Self {
+        Perspective::with_basis(Basis::look_at(eye, target, up, roll), vfov, aspect, height)
+    }
+
+    fn with_basis(basis: Basis, vfov: f32, aspect: f32, height: u32) -> Self {
         let theta = vfov * std::f32::consts::PI / 180.0;
         let half_height = (theta / 2.0).tan();
-        
+
         let viewport_height = 2.0 * half_height;
         let viewport_width = aspect * viewport_height;
 
         let focal_length = 1.;
 
-        let horizontal = Vector::new(viewport_width, 0., 0.);
-        let vertical = Vector::new(0., viewport_height, 0.);
+        let horizontal = basis.u * viewport_width;
+        let vertical = basis.v * viewport_height;
 
         Self {
-            origin,
+            origin: basis.origin,
             horizontal,
             vertical,
-            lower_left_corner: origin - horizontal / 2. - vertical / 2. - Vector::new(0., 0., focal_length),
+            lower_left_corner: basis.origin - horizontal / 2. - vertical / 2. - basis.w * focal_length,
+            basis,
+            aperture: 0.,
+            focus_distance: 1.,
             height,
             width: (height as f32 * aspect) as u32,
         }
     }
 
+    pub fn with_lens(self, aperture: f32, focus_distance: f32) -> Self {
+        Self { aperture, focus_distance, ..self }
+    }
+
+    /// Moves the plane of focus through `point`.
+    pub fn focus_on(self, point: Point) -> Self {
+        Self { focus_distance: (self.origin - point).dot(self.basis.w).abs(), ..self }
+    }
+
     pub fn ray_for_pixel(self, x: u32, y: u32) -> Ray {
-        let u = (x as f32 + 0.5) / self.width as f32;
-        let v = (y as f32 + 0.5) / self.height as f32;
+        self.ray_for_sample(x as f32 + 0.5, y as f32 + 0.5)
+    }
+
+    pub fn ray_for_sample(self, x: f32, y: f32) -> Ray {
+        let u = x / self.width as f32;
+        let v = y / self.height as f32;
 
         let point_on_screen = self.lower_left_corner + u * self.horizontal + v * self.vertical;
 
-        Ray::new(self.origin, point_on_screen - self.origin)
+        if self.aperture <= 0. {
+            return Ray::new(self.origin, point_on_screen - self.origin);
+        }
+
+        // The screen sits at unit distance, so scaling puts the point on the focus plane.
+        let point_in_focus = self.origin + (point_on_screen - self.origin) * self.focus_distance;
+        let (lens_x, lens_y) = sample_disk(&mut rand::thread_rng());
+        let lens_origin = self.origin + self.basis.u * (lens_x * self.aperture) + self.basis.v * (lens_y * self.aperture);
+
+        Ray::new(lens_origin, point_in_focus - lens_origin)
     }
-}
\ No newline at end of file
+}
+
+/// Parallel projection; `view_height` is the world-space height of the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orthographic {
+    basis: Basis,
+    view_height: f32,
+    aspect: f32,
+    width: u32,
+    height: u32,
+}
+
+impl Orthographic {
+    pub fn new(origin: Point, view_height: f32, aspect: f32, height: u32) -> Self {
+        Orthographic::with_basis(default_basis(origin), view_height, aspect, height)
+    }
+
+    pub fn look_at(eye: Point, target: Point, up: Vector, roll: f32, view_height: f32, aspect: f32, height: u32) -> Self {
+        Orthographic::with_basis(Basis::look_at(eye, target, up, roll), view_height, aspect, height)
+    }
+
+    fn with_basis(basis: Basis, view_height: f32, aspect: f32, height: u32) -> Self {
+        Self { basis, view_height, aspect, height, width: (height as f32 * aspect) as u32 }
+    }
+
+    pub fn ray_for_sample(self, x: f32, y: f32) -> Ray {
+        let u = x / self.width as f32 - 0.5;
+        let v = y / self.height as f32 - 0.5;
+        let Basis { origin, u: right, v: up, w } = self.basis;
+
+        let origin = origin + right * (u * self.view_height * self.aspect) + up * (v * self.view_height);
+
+        Ray::new(origin, -w)
+    }
+}
+
+/// Equidistant fisheye: the angle from the view axis grows linearly with the
+/// distance from the image center, reaching `fov / 2` on the inscribed circle.
+/// Samples outside the circle keep extrapolating the same mapping.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fisheye {
+    basis: Basis,
+    fov: f32,
+    width: u32,
+    height: u32,
+}
+
+impl Fisheye {
+    pub fn new(origin: Point, fov: f32, aspect: f32, height: u32) -> Self {
+        Fisheye::with_basis(default_basis(origin), fov, aspect, height)
+    }
+
+    pub fn look_at(eye: Point, target: Point, up: Vector, roll: f32, fov: f32, aspect: f32, height: u32) -> Self {
+        Fisheye::with_basis(Basis::look_at(eye, target, up, roll), fov, aspect, height)
+    }
+
+    fn with_basis(basis: Basis, fov: f32, aspect: f32, height: u32) -> Self {
+        Self { basis, fov, height, width: (height as f32 * aspect) as u32 }
+    }
+
+    pub fn ray_for_sample(self, x: f32, y: f32) -> Ray {
+        let half_extent = self.width.min(self.height) as f32 / 2.;
+        let dx = (x - self.width as f32 / 2.) / half_extent;
+        let dy = (y - self.height as f32 / 2.) / half_extent;
+        let r = (dx * dx + dy * dy).sqrt();
+
+        let theta = r * (self.fov * std::f32::consts::PI / 180.0) / 2.;
+        let phi = dy.atan2(dx);
+        let Basis { origin, u, v, w } = self.basis;
+
+        let direction = u * (theta.sin() * phi.cos()) + v * (theta.sin() * phi.sin()) - w * theta.cos();
+
+        Ray::new(origin, direction)
+    }
+}
+
+/// Full 360° by 180° panorama in latitude-longitude layout; the center of the
+/// image looks towards the target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Equirectangular {
+    basis: Basis,
+    width: u32,
+    height: u32,
+}
+
+impl Equirectangular {
+    pub fn new(origin: Point, aspect: f32, height: u32) -> Self {
+        Equirectangular::with_basis(default_basis(origin), aspect, height)
+    }
+
+    pub fn look_at(eye: Point, target: Point, up: Vector, roll: f32, aspect: f32, height: u32) -> Self {
+        Equirectangular::with_basis(Basis::look_at(eye, target, up, roll), aspect, height)
+    }
+
+    fn with_basis(basis: Basis, aspect: f32, height: u32) -> Self {
+        Self { basis, height, width: (height as f32 * aspect) as u32 }
+    }
+
+    pub fn ray_for_sample(self, x: f32, y: f32) -> Ray {
+        let longitude = (x / self.width as f32 - 0.5) * 2. * std::f32::consts::PI;
+        let latitude = (y / self.height as f32 - 0.5) * std::f32::consts::PI;
+        let Basis { origin, u, v, w } = self.basis;
+
+        let direction = u * (latitude.cos() * longitude.sin()) + v * latitude.sin() - w * (latitude.cos() * longitude.cos());
+
+        Ray::new(origin, direction)
+    }
+}
+
+impl_froms!(Camera: Perspective, Orthographic, Fisheye, Equirectangular);
+
+/// Uniform point on the unit disk using Shirley's concentric mapping.
+fn sample_disk(rng: &mut impl Rng) -> (f32, f32) {
+    let a = 2. * rng.gen::<f32>() - 1.;
+    let b = 2. * rng.gen::<f32>() - 1.;
+    if a == 0. && b == 0. {
+        return (0., 0.);
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, std::f32::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (b, std::f32::consts::FRAC_PI_2 - std::f32::consts::FRAC_PI_4 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: Vector, actual: Vector) {
+        assert!((expected - actual).len() < 1e-5, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn test_new() {
+        let camera: Camera = Perspective::new(Point::new(1., 2., 3.), 90., 1., 100).into();
+        let ray = camera.ray_for_sample(50., 50.);
+        assert_eq!(Point::new(1., 2., 3.), ray.origin);
+        assert_close(Vector::new(0., 0., -1.), ray.direction);
+    }
+
+    #[test]
+    fn test_look_at() {
+        let eye = Point::new(5., 0., 0.);
+        let camera = Perspective::look_at(eye, Point::new(0., 0., 0.), Vector::new(0., 1., 0.), 0., 90., 1., 100);
+        assert_close(Vector::new(-1., 0., 0.), camera.ray_for_sample(50., 50.).direction);
+        assert_close(Vector::new(-1., 0., -1.).normalize(), camera.ray_for_sample(100., 50.).direction);
+        assert_close(Vector::new(-1., 1., 0.).normalize(), camera.ray_for_sample(50., 100.).direction);
+    }
+
+    #[test]
+    fn test_lens() {
+        let camera = Perspective::new(Point::new(0., 0., 0.), 90., 1., 100).with_lens(0.5, 4.);
+        for _ in 0..20 {
+            let ray = camera.ray_for_sample(75., 50.);
+            assert!((ray.origin - camera.origin).len() <= 0.5 + 1e-6);
+            assert_eq!(0., ray.origin.z);
+            let t = -4. / ray.direction.z;
+            assert!((ray.at(t) - Point::new(2., 0., -4.)).len() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_focus_on() {
+        let camera = Perspective::look_at(Point::new(0., 0., 5.), Point::new(0., 0., 0.), Vector::new(0., 1., 0.), 0., 60., 1., 100);
+        assert_eq!(3., camera.focus_on(Point::new(1., 1., 2.)).focus_distance);
+    }
+
+    #[test]
+    fn test_roll() {
+        let eye = Point::new(0., 0., 0.);
+        let camera = Perspective::look_at(eye, Point::new(0., 0., -1.), Vector::new(0., 1., 0.), 90., 90., 1., 100);
+        assert_close(Vector::new(0., 1., -1.).normalize(), camera.ray_for_sample(100., 50.).direction);
+        assert_close(Vector::new(-1., 0., -1.).normalize(), camera.ray_for_sample(50., 100.).direction);
+    }
+
+    #[test]
+    fn test_up_parallel_to_view() {
+        let camera = Perspective::look_at(Point::new(0., 5., 0.), Point::new(0., 0., 0.), Vector::new(0., 1., 0.), 0., 90., 1., 100);
+        assert_close(Vector::new(0., -1., 0.), camera.ray_for_sample(50., 50.).direction);
+        let corner = camera.ray_for_sample(0., 0.).direction;
+        assert!(corner.x.is_finite() && corner.y.is_finite() && corner.z.is_finite());
+    }
+
+    #[test]
+    fn test_orthographic() {
+        let camera: Camera = Orthographic::new(Point::new(0., 0., 0.), 4., 2., 100).into();
+        let corner = camera.ray_for_sample(200., 100.);
+        assert_eq!(Point::new(4., 2., 0.), corner.origin);
+        assert_eq!(Vector::new(0., 0., -1.), corner.direction);
+        assert_eq!(Vector::new(0., 0., -1.), camera.ray_for_sample(17., 3.).direction);
+    }
+
+    #[test]
+    fn test_fisheye() {
+        let camera: Camera = Fisheye::new(Point::new(0., 0., 0.), 180., 1., 100).into();
+        assert_close(Vector::new(0., 0., -1.), camera.ray_for_sample(50., 50.).direction);
+        assert_close(Vector::new(1., 0., 0.), camera.ray_for_sample(100., 50.).direction);
+        assert_close(Vector::new(0., -1., 0.), camera.ray_for_sample(50., 0.).direction);
+        assert_close(Vector::new(1., 0., -1.).normalize(), camera.ray_for_sample(75., 50.).direction);
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let camera: Camera = Equirectangular::new(Point::new(0., 0., 0.), 2., 100).into();
+        assert_close(Vector::new(0., 0., -1.), camera.ray_for_sample(100., 50.).direction);
+        assert_close(Vector::new(1., 0., 0.), camera.ray_for_sample(150., 50.).direction);
+        assert_close(Vector::new(0., 0., 1.), camera.ray_for_sample(0., 50.).direction);
+        assert_close(Vector::new(0., 1., 0.), camera.ray_for_sample(100., 100.).direction);
+    }
+}
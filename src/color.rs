@@ -1,54 +1,181 @@
-use std::ops::{Mul, MulAssign, Add, AddAssign};
+use std::ops::{Mul, MulAssign, Add, AddAssign, Div};
 
+/// Linear, unbounded RGB radiance. Values are only squashed into the
+/// displayable range when converted to 8-bit at output time.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8
+    pub r: f32,
+    pub g: f32,
+    pub b: f32
 }
 
 impl Color {
-    pub fn new(r: u8, g: u8, b: u8) -> Color {
+    pub fn new(r: f32, g: f32, b: f32) -> Color {
         Color { r, g, b }
     }
+
+    pub fn black() -> Color {
+        Color::new(0., 0., 0.)
+    }
+
+    pub fn white() -> Color {
+        Color::new(1., 1., 1.)
+    }
+
+    /// Builds a linear color from 8-bit sRGB-encoded components.
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::from_srgb(r as f32 / 255., g as f32 / 255., b as f32 / 255.)
+    }
+
+    /// Converts sRGB-encoded components in `[0, 1]` to linear color.
+    pub fn from_srgb(r: f32, g: f32, b: f32) -> Color {
+        Color::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+    }
+
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn max_component(self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    pub fn is_black(self) -> bool {
+        self.r <= 0. && self.g <= 0. && self.b <= 0.
+    }
+
+    /// Clamps to [0, 1], applies the sRGB transfer curve and quantizes.
+    pub fn to_srgb8(self) -> [u8; 3] {
+        let encode = |c: f32| (linear_to_srgb(c.clamp(0., 1.)) * 255. + 0.5) as u8;
+        [encode(self.r), encode(self.g), encode(self.b)]
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Operator compressing HDR radiance into [0, 1] before gamma encoding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces
+}
+
+impl ToneMapping {
+    pub fn apply(self, color: Color, exposure: f32) -> Color {
+        let color = color * exposure;
+        match self {
+            ToneMapping::Clamp => color,
+            ToneMapping::Reinhard => Color::new(color.r / (1. + color.r), color.g / (1. + color.g), color.b / (1. + color.b)),
+            ToneMapping::Aces => {
+                // Narkowicz's fit of the ACES filmic curve.
+                let aces = |x: f32| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+                Color::new(aces(color.r), aces(color.g), aces(color.b))
+            }
+        }
+    }
 }
 
 impl From<Color> for image::Rgb<u8> {
     fn from(color: Color) -> Self {
-        image::Rgb([color.r as u8, color.g as u8, color.b as u8])
+        image::Rgb(color.to_srgb8())
     }
 }
 
 impl Add<Color> for Color {
     type Output = Color;
     fn add(self, rhs: Color) -> Self::Output {
-        Color {
-            r: self.r.saturating_add(rhs.r),
-            g: self.g.saturating_add(rhs.g),
-            b: self.b.saturating_add(rhs.b),
-        }
+        Color::new(self.r + rhs.r, self.g + rhs.g, self.b + rhs.b)
     }
 }
 
 impl AddAssign<Color> for Color {
     fn add_assign(&mut self, rhs: Color) {
-        self.r = self.r.saturating_add(rhs.r);
-        self.g = self.g.saturating_add(rhs.g);
-        self.b = self.b.saturating_add(rhs.b);
+        self.r += rhs.r;
+        self.g += rhs.g;
+        self.b += rhs.b;
     }
 }
 
 impl Mul<f32> for Color {
     type Output = Color;
     fn mul(self, rhs: f32) -> Self::Output {
-        Color::new((self.r as f32 * rhs).min(255.) as u8, (self.g as f32 * rhs).min(255.) as u8, (self.b as f32 * rhs).min(255.) as u8)
+        Color::new(self.r * rhs, self.g * rhs, self.b * rhs)
+    }
+}
+
+impl Mul<Color> for Color {
+    type Output = Color;
+    fn mul(self, rhs: Color) -> Self::Output {
+        Color::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b)
     }
 }
 
 impl MulAssign<f32> for Color {
     fn mul_assign(&mut self, rhs: f32) {
-        self.r = (self.r as f32 * rhs).min(255.) as u8;
-        self.g = (self.g as f32 * rhs).min(255.) as u8;
-        self.b = (self.b as f32 * rhs).min(255.) as u8;
+        self.r *= rhs;
+        self.g *= rhs;
+        self.b *= rhs;
+    }
+}
+
+impl Div<f32> for Color {
+    type Output = Color;
+    fn div(self, rhs: f32) -> Self::Output {
+        Color::new(self.r / rhs, self.g / rhs, self.b / rhs)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        let mut color = Color::new(0.8, 0.5, 0.);
+        color += Color::new(0.8, 0.5, 0.25);
+        assert_eq!(Color::new(1.6, 1., 0.25), color);
+    }
+
+    #[test]
+    fn test_mul_color() {
+        let color = Color::new(0.1, 0.5, 2.);
+        assert_eq!(color, Color::white() * color);
+        assert_eq!(Color::new(0., 0.25, 1.), color * Color::new(0., 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_to_srgb8() {
+        assert_eq!([0, 255, 255], Color::new(-1., 1., 3.).to_srgb8());
+        assert_eq!([188, 188, 188], Color::new(0.5, 0.5, 0.5).to_srgb8());
+    }
+
+    #[test]
+    fn test_from_srgb8() {
+        assert_eq!([10, 128, 250], Color::from_srgb8(10, 128, 250).to_srgb8());
+    }
+
+    #[test]
+    fn test_tone_mapping() {
+        let color = Color::new(1., 3., 0.);
+        assert_eq!(Color::new(0.5, 0.75, 0.), ToneMapping::Reinhard.apply(color, 1.));
+        assert_eq!(Color::new(2., 6., 0.), ToneMapping::Clamp.apply(color, 2.));
+        let aces = ToneMapping::Aces.apply(Color::new(100., 0., 0.), 1.);
+        assert!(aces.r > 0.99 && aces.r <= 1.05);
+        assert_eq!(0., aces.g);
+    }
+}
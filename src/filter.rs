@@ -0,0 +1,87 @@
+/// Reconstruction filter used to weight the samples that contribute to a pixel.
+/// Offsets are measured in pixels from the pixel center.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell
+}
+
+impl Filter {
+    /// Half-width of the filter support in pixels.
+    pub fn radius(self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.
+        }
+    }
+
+    pub fn weight(self, dx: f32, dy: f32) -> f32 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(self, x: f32) -> f32 {
+        let x = x.abs();
+        let radius = self.radius();
+        if x > radius {
+            return 0.;
+        }
+
+        match self {
+            Filter::Box => 1.,
+            Filter::Tent => radius - x,
+            Filter::Gaussian => {
+                let alpha = 2.;
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.)
+            },
+            Filter::Mitchell => mitchell(x, 1. / 3., 1. / 3.)
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic over |x| in [0, 2].
+fn mitchell(x: f32, b: f32, c: f32) -> f32 {
+    let weight = if x < 1. {
+        (12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b)
+    } else {
+        (-b - 6. * c) * x * x * x + (6. * b + 30. * c) * x * x + (-12. * b - 48. * c) * x + (8. * b + 24. * c)
+    };
+    weight / 6.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box() {
+        assert_eq!(1., Filter::Box.weight(0.4, -0.4));
+        assert_eq!(0., Filter::Box.weight(0.6, 0.));
+    }
+
+    #[test]
+    fn test_tent() {
+        assert_eq!(1., Filter::Tent.weight(0., 0.));
+        assert_eq!(0.25, Filter::Tent.weight(0.5, -0.5));
+        assert_eq!(0., Filter::Tent.weight(1.5, 0.));
+    }
+
+    #[test]
+    fn test_gaussian() {
+        let center = Filter::Gaussian.weight(0., 0.);
+        let off_center = Filter::Gaussian.weight(0.5, 0.);
+        assert!(center > off_center && off_center > 0.);
+        assert_eq!(0., Filter::Gaussian.weight(1.5, 0.));
+    }
+
+    #[test]
+    fn test_mitchell() {
+        assert!((Filter::Mitchell.weight(0., 0.) - (8. / 9.) * (8. / 9.)).abs() < 1e-6);
+        assert!(Filter::Mitchell.weight(1.5, 0.) < 0.);
+        assert!(Filter::Mitchell.weight(2., 0.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,547 @@
+//! glTF 2.0 scene import. Metallic-roughness materials are approximated with
+//! [`Material`]; metallic-roughness, normal and occlusion textures are ignored.
+
+use std::{collections::HashMap, fmt, path::{Path, PathBuf}};
+
+use ::gltf::{camera::Projection, khr_lights_punctual::Kind, material::AlphaMode, mesh::Mode, image::Format, texture::{Info, WrappingMode}};
+
+use crate::{
+    scene::Scene, camera::{Camera, Perspective, Orthographic}, point::Point, vector::Vector, color::Color,
+    material::Material, texture::{Texture, Wrap}, mesh::{Mesh, Face}, light::{Directional, PointLight, SpotLight},
+    aabb::{Bounded, AABB}, EPSILON
+};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import(PathBuf, ::gltf::Error),
+    Unsupported(PathBuf, String)
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Import(path, error) => write!(f, "{}: {}", path.display(), error),
+            GltfError::Unsupported(path, message) => write!(f, "{}: {}", path.display(), message)
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+/// Column-major affine transform as stored in glTF.
+type Transform = [[f32; 4]; 4];
+
+const IDENTITY: Transform = [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]];
+
+fn multiply(a: &Transform, b: &Transform) -> Transform {
+    let mut result = [[0.; 4]; 4];
+    for (column, b_column) in result.iter_mut().zip(b) {
+        for (row, value) in column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b_column[k]).sum();
+        }
+    }
+    result
+}
+
+fn transform_point(m: &Transform, p: [f32; 3]) -> Point {
+    let [x, y, z] = p;
+    Point::new(
+        m[0][0] * x + m[1][0] * y + m[2][0] * z + m[3][0],
+        m[0][1] * x + m[1][1] * y + m[2][1] * z + m[3][1],
+        m[0][2] * x + m[1][2] * y + m[2][2] * z + m[3][2]
+    )
+}
+
+fn transform_vector(m: &Transform, v: [f32; 3]) -> Vector {
+    let [x, y, z] = v;
+    Vector::new(
+        m[0][0] * x + m[1][0] * y + m[2][0] * z,
+        m[0][1] * x + m[1][1] * y + m[2][1] * z,
+        m[0][2] * x + m[1][2] * y + m[2][2] * z
+    )
+}
+
+/// Determinant of the upper 3x3 block, negative for mirroring transforms.
+fn determinant(m: &Transform) -> f32 {
+    let column = |i: usize| Vector::new(m[i][0], m[i][1], m[i][2]);
+    column(0).dot(column(1).cross(column(2)))
+}
+
+/// Transforms normals with the inverse transpose of the upper 3x3 block,
+/// which is proportional to its cofactor matrix.
+fn transform_normal(m: &Transform, n: [f32; 3]) -> Vector {
+    let column = |i: usize| Vector::new(m[i][0], m[i][1], m[i][2]);
+    let (x, y, z) = (column(0), column(1), column(2));
+    let (cx, cy, cz) = (y.cross(z), z.cross(x), x.cross(y));
+    let sign = if x.dot(cx) < 0. { -1. } else { 1. };
+    (cx * n[0] + cy * n[1] + cz * n[2]) * sign
+}
+
+/// An imported scene together with the image it should be rendered to.
+pub struct GltfScene {
+    pub scene: Scene,
+    pub width: u32,
+    pub height: u32
+}
+
+/// Imports the default scene of a glTF file, taking the missing width from its camera.
+pub fn load<P: AsRef<Path>>(path: P, width: Option<u32>, height: u32) -> Result<GltfScene, GltfError> {
+    let path = path.as_ref();
+    let (document, buffers, images) = ::gltf::import(path).map_err(|error| GltfError::Import(path.to_path_buf(), error))?;
+
+    let mut importer = Importer {
+        file: path,
+        buffers: &buffers,
+        images: &images,
+        scene: Scene::new(Perspective::new(Point::new(0., 0., 0.), 60., 1., height).into(), vec![], vec![]),
+        camera: None,
+        materials: HashMap::new(),
+        textures: HashMap::new()
+    };
+
+    let scene = document.default_scene().or_else(|| document.scenes().next());
+    if let Some(scene) = scene {
+        for node in scene.nodes() {
+            importer.node(&node, &IDENTITY)?;
+        }
+    }
+
+    let camera = importer.camera.and_then(|(index, transform)| Some((document.cameras().nth(index)?, transform)));
+    let width = width.unwrap_or_else(|| {
+        let aspect = match camera.as_ref().map(|(camera, _)| camera.projection()) {
+            Some(Projection::Perspective(perspective)) => perspective.aspect_ratio().unwrap_or(1.),
+            Some(Projection::Orthographic(orthographic)) => orthographic.xmag() / orthographic.ymag(),
+            None => 1.
+        };
+        ((height as f32 * aspect).round() as u32).max(1)
+    });
+    let aspect = width as f32 / height as f32;
+
+    let mut scene = importer.scene;
+    scene.camera = match camera {
+        Some((camera, transform)) => gltf_camera(&camera, &transform, aspect, height),
+        None => framing_camera(&scene, aspect, height)
+    };
+    Ok(GltfScene { scene, width, height })
+}
+
+fn gltf_camera(camera: &::gltf::Camera, transform: &Transform, aspect: f32, height: u32) -> Camera {
+    let eye = transform_point(transform, [0., 0., 0.]);
+    let target = eye + transform_vector(transform, [0., 0., -1.]);
+    let up = transform_vector(transform, [0., 1., 0.]);
+    match camera.projection() {
+        Projection::Perspective(perspective) => Perspective::look_at(eye, target, up, 0., perspective.yfov().to_degrees(), aspect, height).into(),
+        Projection::Orthographic(orthographic) => Orthographic::look_at(eye, target, up, 0., 2. * orthographic.ymag(), aspect, height).into()
+    }
+}
+
+/// Camera on the +Z side of the scene bounds looking down -Z, like the
+/// default glTF camera, far enough back to see everything.
+fn framing_camera(scene: &Scene, aspect: f32, height: u32) -> Camera {
+    let vfov: f32 = 60.;
+    let bounds = scene.objects.iter().map(|object| object.aabb()).chain(scene.meshes.iter().map(|mesh| mesh.aabb()));
+    let (target, radius) = match bounds.reduce(AABB::merge) {
+        Some(bounds) => (bounds.center(), (bounds.size().len() * 0.5).max(EPSILON)),
+        None => (Point::new(0., 0., 0.), 1.)
+    };
+    let distance = radius / (vfov.to_radians() * 0.5).sin();
+    let eye = target + Vector::new(0., 0., distance);
+    Perspective::look_at(eye, target, Vector::new(0., 1., 0.), 0., vfov, aspect, height).into()
+}
+
+struct Importer<'a> {
+    file: &'a Path,
+    buffers: &'a [::gltf::buffer::Data],
+    images: &'a [::gltf::image::Data],
+    scene: Scene,
+    /// Index and world transform of the first camera found.
+    camera: Option<(usize, Transform)>,
+    /// Scene material ids by glTF material index; `None` is the glTF default material.
+    materials: HashMap<Option<usize>, usize>,
+    /// Scene texture ids by glTF texture index.
+    textures: HashMap<usize, usize>
+}
+
+impl Importer<'_> {
+    fn unsupported(&self, message: String) -> GltfError {
+        GltfError::Unsupported(self.file.to_path_buf(), message)
+    }
+
+    fn node(&mut self, node: &::gltf::Node, parent: &Transform) -> Result<(), GltfError> {
+        let transform = multiply(parent, &node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.primitive(&primitive, &transform)?;
+            }
+        }
+        if let Some(camera) = node.camera() {
+            if self.camera.is_none() {
+                self.camera = Some((camera.index(), transform));
+            }
+        }
+        if let Some(light) = node.light() {
+            self.light(&light, &transform);
+        }
+        for child in node.children() {
+            self.node(&child, &transform)?;
+        }
+        Ok(())
+    }
+
+    fn light(&mut self, light: &::gltf::khr_lights_punctual::Light, transform: &Transform) {
+        let [r, g, b] = light.color();
+        let color = Color::new(r, g, b);
+        let position = transform_point(transform, [0., 0., 0.]);
+        let direction = transform_vector(transform, [0., 0., -1.]);
+        let light = match light.kind() {
+            Kind::Directional => Directional::new(direction, light.intensity()).with_color(color).into(),
+            Kind::Point => PointLight::new(position, light.intensity()).with_color(color).into(),
+            Kind::Spot { inner_cone_angle, outer_cone_angle } =>
+                SpotLight::new(position, direction, light.intensity(), inner_cone_angle, outer_cone_angle).with_color(color).into()
+        };
+        self.scene.add_light(light);
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> Result<usize, GltfError> {
+        if let Some(&id) = self.materials.get(&material.index()) {
+            return Ok(id);
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, alpha] = pbr.base_color_factor();
+        let metallic = pbr.metallic_factor();
+        let roughness = pbr.roughness_factor();
+        let [er, eg, eb] = material.emissive_factor();
+        let emissive_strength = material.emissive_strength().unwrap_or(1.);
+
+        let mut result = Material::new(Color::new(r, g, b))
+            .with_specular(0.04 + 0.96 * metallic, roughness)
+            .with_reflectivity(metallic * (1. - roughness) * (1. - roughness))
+            .with_emission(Color::new(er, eg, eb) * emissive_strength);
+        if material.alpha_mode() == AlphaMode::Blend {
+            result = result.with_opacity(alpha);
+        }
+        if material.transmission().is_some_and(|transmission| transmission.transmission_factor() > 0.) {
+            result = result.with_ior(material.ior().unwrap_or(1.5));
+        }
+        if let Some(info) = pbr.base_color_texture() {
+            result = result.with_albedo_texture(self.texture(&info, "base color")?);
+        }
+        if let Some(info) = material.emissive_texture() {
+            result = result.with_emission_texture(self.texture(&info, "emissive")?);
+        }
+
+        let id = self.scene.add_material(result);
+        self.materials.insert(material.index(), id);
+        Ok(id)
+    }
+
+    fn texture(&mut self, info: &Info, name: &str) -> Result<usize, GltfError> {
+        if info.tex_coord() != 0 {
+            return Err(self.unsupported(format!("{} texture uses TEXCOORD_{}, only TEXCOORD_0 is supported", name, info.tex_coord())));
+        }
+        let texture = info.texture();
+        if let Some(&id) = self.textures.get(&texture.index()) {
+            return Ok(id);
+        }
+
+        let image = texture.source().index();
+        let data = &self.images[image];
+        let (channels, bytes_per_channel) = match data.format {
+            Format::R8 => (1, 1),
+            Format::R8G8 => (2, 1),
+            Format::R8G8B8 => (3, 1),
+            Format::R8G8B8A8 => (4, 1),
+            Format::R16 => (1, 2),
+            Format::R16G16 => (2, 2),
+            Format::R16G16B16 => (3, 2),
+            Format::R16G16B16A16 => (4, 2),
+            format => return Err(self.unsupported(format!("image {} has unsupported format {:?}", image, format)))
+        };
+        let texels = data.pixels
+            .chunks_exact(channels * bytes_per_channel)
+            .map(|pixel| {
+                // The decoder hands over 16-bit channels in native byte order.
+                let channel = |c: usize| {
+                    let offset = c.min(channels - 1) * bytes_per_channel;
+                    if bytes_per_channel == 1 {
+                        pixel[offset] as f32 / 255.
+                    } else {
+                        u16::from_ne_bytes([pixel[offset], pixel[offset + 1]]) as f32 / 65535.
+                    }
+                };
+                if channels < 3 {
+                    Color::from_srgb(channel(0), channel(0), channel(0))
+                } else {
+                    Color::from_srgb(channel(0), channel(1), channel(2))
+                }
+            })
+            .collect();
+
+        let wrap = |mode| match mode {
+            WrappingMode::Repeat => Wrap::Repeat,
+            WrappingMode::ClampToEdge => Wrap::Clamp,
+            WrappingMode::MirroredRepeat => Wrap::Mirror
+        };
+        let sampler = texture.sampler();
+        let result = Texture::new(data.width, data.height, texels).with_wrap(wrap(sampler.wrap_s()), wrap(sampler.wrap_t()));
+        let id = self.scene.add_texture(result);
+        self.textures.insert(texture.index(), id);
+        Ok(id)
+    }
+
+    fn primitive(&mut self, primitive: &::gltf::Primitive, transform: &Transform) -> Result<(), GltfError> {
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let positions: Vec<Point> = match reader.read_positions() {
+            Some(positions) => positions.map(|position| transform_point(transform, position)).collect(),
+            None => return Ok(())
+        };
+        let normals: Option<Vec<Vector>> = reader.read_normals()
+            .map(|normals| normals.map(|normal| transform_normal(transform, normal)).collect());
+        // glTF puts the texture origin at the top left, textures here expect it at the bottom left.
+        let uvs: Option<Vec<(f32, f32)>> = reader.read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| (u, 1. - v)).collect());
+        let colors: Option<Vec<Color>> = reader.read_colors(0)
+            .map(|colors| colors.into_rgb_f32().map(|[r, g, b]| Color::new(r, g, b)).collect());
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect()
+        };
+        let attribute_lengths = [normals.as_ref().map(Vec::len), uvs.as_ref().map(Vec::len), colors.as_ref().map(Vec::len)];
+        if attribute_lengths.iter().flatten().any(|&length| length != positions.len()) {
+            return Err(self.unsupported("vertex attributes have different lengths".to_string()));
+        }
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= positions.len()) {
+            return Err(self.unsupported(format!("vertex index {} is out of range, {} vertices", index, positions.len())));
+        }
+
+        let mut corners: Vec<[u32; 3]> = match primitive.mode() {
+            Mode::Triangles => indices.chunks_exact(3).map(|chunk| [chunk[0], chunk[1], chunk[2]]).collect(),
+            Mode::TriangleStrip => (2..indices.len())
+                .map(|i| if i % 2 == 0 { [indices[i - 2], indices[i - 1], indices[i]] } else { [indices[i - 1], indices[i - 2], indices[i]] })
+                .collect(),
+            Mode::TriangleFan => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+            // Points and lines have no surface to render.
+            _ => return Ok(())
+        };
+        // A mirroring transform turns faces inside out unless their winding flips with it.
+        if determinant(transform) < 0. {
+            for face in &mut corners {
+                face.swap(1, 2);
+            }
+        }
+
+        let material = self.material(&primitive.material())?;
+        let mut mesh = Mesh::new(positions, corners.into_iter().map(|corners| Face::new(corners).with_material(material)).collect());
+        if let Some(normals) = normals {
+            mesh = mesh.with_normals(normals);
+        }
+        if let Some(uvs) = uvs {
+            mesh = mesh.with_uvs(uvs);
+        }
+        if let Some(colors) = colors {
+            mesh = mesh.with_colors(colors);
+        }
+        self.scene.add_mesh(mesh);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn buffer() -> Vec<u8> {
+        let values: [f32; 18] = [0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 1., 0., 0., 1.];
+        values.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    /// A red triangle scaled by 2 under a parent node moved to z = -5, a
+    /// camera at z = 1 and a directional light pointing down.
+    fn document(buffer_uri: Option<&str>, with_camera: bool) -> String {
+        let uri = buffer_uri.map(|uri| format!(r#""uri": "{}","#, uri)).unwrap_or_default();
+        let nodes = if with_camera { "[0, 2, 3]" } else { "[0, 3]" };
+        format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "extensionsUsed": ["KHR_lights_punctual"],
+            "extensions": {{ "KHR_lights_punctual": {{ "lights": [{{ "type": "directional", "intensity": 3, "color": [1, 0.5, 1] }}] }} }},
+            "scene": 0,
+            "scenes": [{{ "nodes": {} }}],
+            "nodes": [
+                {{ "translation": [0, 0, -5], "children": [1] }},
+                {{ "mesh": 0, "scale": [2, 2, 2] }},
+                {{ "camera": 0, "translation": [0, 0, 1] }},
+                {{ "rotation": [-0.70710677, 0, 0, 0.70710677], "extensions": {{ "KHR_lights_punctual": {{ "light": 0 }} }} }}
+            ],
+            "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 1.0, "znear": 0.1 }} }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "material": 0 }}] }}],
+            "materials": [{{ "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 1, "roughnessFactor": 0.5 }} }}],
+            "buffers": [{{ {} "byteLength": 72 }}],
+            "bufferViews": [{{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }}, {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }}
+            ]
+        }}"#, nodes, uri)
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().div_ceil(4) * 4, 0);
+
+        let mut bytes = b"glTF".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        bytes.extend((json.len() as u32).to_le_bytes());
+        bytes.extend(b"JSON");
+        bytes.extend(json);
+        bytes.extend((bin.len() as u32).to_le_bytes());
+        bytes.extend(b"BIN\0");
+        bytes.extend(bin);
+        bytes
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gltf-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_gltf() {
+        let dir = temp_dir("separate");
+        fs::write(dir.join("triangle.bin"), buffer()).unwrap();
+        fs::write(dir.join("scene.gltf"), document(Some("triangle.bin"), true)).unwrap();
+        let GltfScene { scene, width, height } = load(dir.join("scene.gltf"), Some(200), 100).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!((200, 100), (width, height));
+
+        assert_eq!(1, scene.meshes.len());
+        assert_eq!(1, scene.meshes[0].faces.len());
+        let triangle = scene.meshes[0].triangle(0);
+        assert_eq!(Point::new(0., 0., -5.), triangle.v0);
+        assert_eq!(Point::new(2., 0., -5.), triangle.v1);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), triangle.n1);
+
+        let material = scene.material(triangle.into());
+        assert_eq!(Color::new(1., 0., 0.), material.albedo);
+        assert_eq!(0.5, material.roughness);
+        assert_eq!(1., material.specular);
+        assert_eq!(0.25, material.reflectivity);
+
+        match scene.camera {
+            Camera::Perspective(camera) => assert_eq!(Point::new(0., 0., 1.), camera.origin),
+            _ => panic!("expected a perspective camera")
+        }
+
+        assert_eq!(1, scene.lights.len());
+        let sample = scene.lights[0].sample(Point::new(0., 0., 0.));
+        assert!((sample.direction - Vector::new(0., 1., 0.)).len() < 1e-6);
+        assert_eq!(Color::new(3., 1.5, 3.), sample.radiance);
+    }
+
+    #[test]
+    fn test_load_glb() {
+        let dir = temp_dir("binary");
+        fs::write(dir.join("scene.glb"), glb(&document(None, false), &buffer())).unwrap();
+        let GltfScene { scene, width, .. } = load(dir.join("scene.glb"), None, 100).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(100, width);
+
+        assert_eq!(1, scene.meshes.len());
+        // Without a camera the scene is framed from +Z.
+        match scene.camera {
+            Camera::Perspective(camera) => {
+                assert_eq!(1., camera.origin.x);
+                assert_eq!(1., camera.origin.y);
+                assert!(camera.origin.z > -5.);
+            },
+            _ => panic!("expected a perspective camera")
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(load("missing.gltf", None, 1), Err(GltfError::Import(..))));
+    }
+
+    /// `document` with a 16-bit PNG texture clamped in s and mirrored in t,
+    /// mapped with `mapping` such as `"emissiveTexture"` inside the JSON
+    /// object opened by `within`.
+    fn textured_scene(name: &str, within: &str, mapping: &str) -> Result<GltfScene, GltfError> {
+        let dir = temp_dir(name);
+        fs::write(dir.join("triangle.bin"), buffer()).unwrap();
+        // High and low bytes differ, so the byte order shows in the result.
+        let texels = vec![0x8000u16, 0x00ff, 0xffff];
+        image::ImageBuffer::<image::Rgb<u16>, _>::from_raw(1, 1, texels).unwrap().save(dir.join("texture.png")).unwrap();
+        let textures = r#""samplers": [{ "wrapS": 33071, "wrapT": 33648 }], "images": [{ "uri": "texture.png" }], "textures": [{ "source": 0, "sampler": 0 }], "buffers""#;
+        let json = document(Some("triangle.bin"), false)
+            .replace(r#""buffers""#, textures)
+            .replace(within, &format!(r#"{}"{}": {{ "index": 0 }}, "#, within, mapping))
+            .replace(r#""materials": [{ "#, r#""materials": [{ "emissiveFactor": [1, 1, 1], "#);
+        fs::write(dir.join("scene.gltf"), json).unwrap();
+        let scene = load(dir.join("scene.gltf"), None, 100);
+        fs::remove_dir_all(dir).unwrap();
+        scene
+    }
+
+    #[test]
+    fn test_textures() {
+        let material_object = r#""materials": [{ "#;
+        let scene = textured_scene("emissive", material_object, "emissiveTexture").unwrap().scene;
+        let material = scene.materials.last().unwrap();
+        let texture = &scene.textures[material.emission_texture.unwrap()];
+        assert_eq!((Wrap::Clamp, Wrap::Mirror), (texture.wrap_u, texture.wrap_v));
+        let expected = Color::from_srgb(0x8000 as f32 / 65535., 0x00ff as f32 / 65535., 1.);
+        let sample = texture.sample(0.5, 0.5);
+        assert!((sample.r - expected.r).abs() < 1e-6 && (sample.g - expected.g).abs() < 1e-6 && sample.b == 1., "{:?}", sample);
+
+        // Normal and metallic-roughness maps are left out rather than failing the import.
+        let pbr_object = r#""pbrMetallicRoughness": { "#;
+        for (within, mapping) in [(material_object, "normalTexture"), (pbr_object, "metallicRoughnessTexture")] {
+            let scene = textured_scene(mapping, within, mapping).unwrap().scene;
+            let material = scene.materials.last().unwrap();
+            assert_eq!((1., 0.5), (material.specular, material.roughness));
+        }
+    }
+
+    #[test]
+    fn test_camera_aspect_ratio() {
+        let dir = temp_dir("aspect");
+        let json = document(None, true).replace(r#""yfov": 1.0"#, r#""aspectRatio": 1.5, "yfov": 1.0"#);
+        fs::write(dir.join("scene.glb"), glb(&json, &buffer())).unwrap();
+        let GltfScene { width, height, .. } = load(dir.join("scene.glb"), None, 100).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!((150, 100), (width, height));
+    }
+
+    #[test]
+    fn test_mirrored_winding() {
+        let dir = temp_dir("mirrored");
+        let json = document(None, false).replace(r#""scale": [2, 2, 2]"#, r#""scale": [-2, 2, 2]"#);
+        fs::write(dir.join("scene.glb"), glb(&json, &buffer())).unwrap();
+        let scene = load(dir.join("scene.glb"), None, 100).unwrap().scene;
+        fs::remove_dir_all(dir).unwrap();
+
+        // The winding still agrees with the normals after the mirror.
+        let triangle = scene.meshes[0].triangle(0);
+        let face_normal = (triangle.v1 - triangle.v0).cross(triangle.v2 - triangle.v0);
+        assert!(face_normal.dot(triangle.n1.unwrap()) > 0.);
+        assert_eq!(Point::new(-2., 0., -5.), triangle.v2);
+    }
+
+    #[test]
+    fn test_transform_normal() {
+        let scale = [[2., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]];
+        // Normals of a surface stretched along x lean away from x.
+        let normal = transform_normal(&scale, [1., 1., 0.]);
+        assert_eq!(Vector::new(1., 2., 0.), normal);
+        let mirror = [[-1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]];
+        assert_eq!(Vector::new(-1., 0., 0.), transform_normal(&mirror, [1., 0., 0.]));
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use crate::{aabb::{AABB, Bounded}, intersectable::Intersectable, intersection::Intersection, matrix::Matrix, mesh::Mesh, ray::Ray, triangle::Triangle};
+
+/// A shared mesh placed in the scene by a transform, so the same mesh can
+/// appear many times while its vertices are stored once.
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub mesh: Arc<Mesh>,
+    transform: Matrix,
+    inverse: Matrix,
+    normal_matrix: Matrix
+}
+
+impl Instance {
+    /// Places `mesh` with `transform`, which must be invertible.
+    pub fn new(mesh: Arc<Mesh>, transform: Matrix) -> Instance {
+        let inverse = transform.inverse().expect("instance transforms must be invertible");
+        let normal_matrix = inverse.transpose();
+        Instance { mesh, transform, inverse, normal_matrix }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    /// The ray in the mesh's own space. Its direction is left unnormalized
+    /// so that distances along it are the same as along `ray`.
+    pub fn object_ray(&self, ray: Ray) -> Ray {
+        Ray {
+            origin: self.inverse.transform_point(ray.origin),
+            direction: self.inverse.transform_vector(ray.direction)
+        }
+    }
+
+    /// Moves an intersection found with [`Instance::object_ray`] back into
+    /// the scene, along with the triangle that was hit.
+    pub fn world_intersection(&self, intersection: Intersection) -> Intersection {
+        let object = match intersection.object {
+            Intersectable::Triangle(triangle) => self.world_triangle(triangle).into(),
+            object => object
+        };
+        Intersection {
+            point: self.transform.transform_point(intersection.point),
+            object,
+            ..intersection
+        }
+    }
+
+    fn world_triangle(&self, triangle: Triangle) -> Triangle {
+        let normal = |n| self.normal_matrix.transform_vector(n).normalize();
+        Triangle {
+            v0: self.transform.transform_point(triangle.v0),
+            v1: self.transform.transform_point(triangle.v1),
+            v2: self.transform.transform_point(triangle.v2),
+            n1: triangle.n1.map(normal),
+            n2: triangle.n2.map(normal),
+            n3: triangle.n3.map(normal),
+            ..triangle
+        }
+    }
+
+    pub fn intersect(&self, face: usize, ray: Ray) -> Option<Intersection> {
+        self.mesh.intersect(face, self.object_ray(ray)).map(|intersection| self.world_intersection(intersection))
+    }
+}
+
+impl Bounded for Instance {
+    fn aabb(&self) -> AABB {
+        self.mesh.aabb().apply_transform(&self.transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{point::Point, vector::Vector};
+
+    use super::*;
+
+    fn triangle_mesh() -> Arc<Mesh> {
+        let triangle = Triangle::with_normals(
+            Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.),
+            Vector::new(1., 0., 1.).normalize(), Vector::new(0., 0., 1.), Vector::new(0., 0., 1.)
+        );
+        Arc::new(Mesh::from_triangles(&[triangle]))
+    }
+
+    #[test]
+    fn test_object_ray() {
+        let instance = Instance::new(triangle_mesh(), Matrix::translate(0., 0., -5.).multiply(&Matrix::scale(2., 2., 2.)));
+        let ray = Ray::new(Point::new(0.5, 0.5, 5.), Vector::new(0., 0., -1.));
+        let local = instance.object_ray(ray);
+        assert_eq!(Point::new(0.25, 0.25, 5.), local.origin);
+        assert_eq!(Vector::new(0., 0., -0.5), local.direction);
+        // Ten units along either ray reach the same place.
+        assert_eq!(instance.transform().transform_point(local.at(10.)), ray.at(10.));
+    }
+
+    #[test]
+    fn test_intersect() {
+        let instance = Instance::new(triangle_mesh(), Matrix::translate(0., 0., -5.).multiply(&Matrix::scale(4., 2., 1.)));
+        let ray = Ray::new(Point::new(1., 0.2, 5.), Vector::new(0., 0., -1.));
+        let intersection = instance.intersect(0, ray).unwrap();
+        assert!((intersection.t - 10.).abs() < 1e-5);
+        assert!((intersection.point - Point::new(1., 0.2, -5.)).len() < 1e-5);
+
+        // The hit triangle and its normals are in scene space.
+        match intersection.object {
+            Intersectable::Triangle(triangle) => assert_eq!(Point::new(4., 0., -5.), triangle.v1),
+            _ => panic!("expected a triangle")
+        }
+        let expected = Vector::new(0.25, 0., 1.).normalize();
+        assert!((intersection.object.normal_at(intersection.point, (0., 0.)) - expected).len() < 1e-6);
+
+        assert!(instance.intersect(0, Ray::new(Point::new(3., 1.5, 5.), Vector::new(0., 0., -1.))).is_none());
+    }
+
+    #[test]
+    fn test_aabb() {
+        let instance = Instance::new(triangle_mesh(), Matrix::translate(1., 0., 0.).multiply(&Matrix::rotate_z(std::f32::consts::FRAC_PI_2)));
+        let aabb = instance.aabb();
+        assert!((aabb.min - Point::new(0., 0., 0.)).len() < 1e-6);
+        assert!((aabb.max - Point::new(1., 1., 0.)).len() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_singular_transform() {
+        Instance::new(triangle_mesh(), Matrix::scale(1., 0., 1.));
+    }
+}
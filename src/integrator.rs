@@ -0,0 +1,454 @@
+use std::f32::consts::PI;
+
+use rand::Rng;
+
+use crate::{scene::Scene, bvh::BVH, ray::Ray, color::Color, vector::Vector, point::Point, material::Material, impl_froms, EPSILON};
+
+/// Distance rays are pushed off a surface to avoid hitting it again.
+const RAY_OFFSET: f32 = 0.0001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    Whitted(Whitted),
+    PathTracer(PathTracer)
+}
+
+impl Integrator {
+    pub fn radiance(&self, scene: &Scene, tree: &BVH, ray: Ray) -> Color {
+        match self {
+            Integrator::Whitted(whitted) => whitted.radiance(scene, tree, ray),
+            Integrator::PathTracer(path_tracer) => path_tracer.radiance(scene, tree, ray)
+        }
+    }
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Whitted::new(5).into()
+    }
+}
+
+/// Classic recursive ray tracer: direct lighting at every hit plus perfect
+/// mirror reflection and Fresnel-weighted refraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Whitted {
+    pub max_depth: u32
+}
+
+impl Whitted {
+    pub fn new(max_depth: u32) -> Whitted {
+        Whitted { max_depth }
+    }
+
+    pub fn radiance(self, scene: &Scene, tree: &BVH, ray: Ray) -> Color {
+        self.trace(scene, tree, ray, 0)
+    }
+
+    fn trace(self, scene: &Scene, tree: &BVH, ray: Ray, depth: u32) -> Color {
+        let intersection = match tree.intersect(ray) {
+            Some(intersection) => intersection,
+            None => return Color::black()
+        };
+        let point = intersection.point;
+        let (material, normal) = scene.surface(&intersection);
+        let can_recurse = depth < self.max_depth;
+
+        let mut color = material.emission;
+
+        if material.is_dielectric() {
+            let entering = ray.direction.dot(normal) < 0.;
+            let (facing_normal, eta) = if entering {
+                (normal, 1. / material.ior)
+            } else {
+                (-normal, material.ior)
+            };
+
+            if !can_recurse {
+                return color + direct_lighting(scene, tree, &material, point, facing_normal, -ray.direction);
+            }
+
+            let reflected = offset_ray(point, ray.direction.reflect(facing_normal), facing_normal);
+            let reflection = self.trace(scene, tree, reflected, depth + 1);
+
+            color += match ray.direction.refract(facing_normal, eta) {
+                Some(direction) => {
+                    let fresnel = fresnel(-ray.direction.dot(facing_normal), direction.dot(-facing_normal), eta);
+                    let refracted = offset_ray(point, direction, facing_normal);
+                    let transmission = self.trace(scene, tree, refracted, depth + 1);
+                    material.albedo * (reflection * fresnel + transmission * (1. - fresnel))
+                },
+                None => material.albedo * reflection
+            };
+        } else {
+            let facing_normal = if ray.direction.dot(normal) > 0. { -normal } else { normal };
+            let local = direct_lighting(scene, tree, &material, point, facing_normal, -ray.direction);
+
+            if material.reflectivity > 0. && can_recurse {
+                let reflected = offset_ray(point, ray.direction.reflect(facing_normal), facing_normal);
+                let reflection = self.trace(scene, tree, reflected, depth + 1);
+                color += local * (1. - material.reflectivity) + material.albedo * reflection * material.reflectivity;
+            } else {
+                color += local;
+            }
+        }
+
+        if material.opacity < 1. && can_recurse {
+            let through = self.trace(scene, tree, offset_ray(point, ray.direction, normal), depth + 1);
+            color = color * material.opacity + through * (1. - material.opacity);
+        }
+
+        color
+    }
+}
+
+/// Monte Carlo estimator of global illumination. Bounces sample the same
+/// BRDF that lights are evaluated with at every non-mirror hit, and paths
+/// are terminated by Russian roulette once they get long or cut off at
+/// `max_depth`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathTracer {
+    pub max_depth: u32,
+    /// Bounce after which Russian roulette may terminate the path.
+    pub roulette_depth: u32
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u32) -> PathTracer {
+        PathTracer { max_depth, roulette_depth: 3 }
+    }
+
+    pub fn with_roulette_depth(self, roulette_depth: u32) -> PathTracer {
+        PathTracer { roulette_depth, ..self }
+    }
+
+    pub fn radiance(self, scene: &Scene, tree: &BVH, ray: Ray) -> Color {
+        self.trace(scene, tree, ray, &mut rand::thread_rng())
+    }
+
+    fn trace(self, scene: &Scene, tree: &BVH, ray: Ray, rng: &mut impl Rng) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = ray;
+
+        for depth in 0..=self.max_depth {
+            let intersection = match tree.intersect(ray) {
+                Some(intersection) => intersection,
+                None => break
+            };
+            let point = intersection.point;
+            let (material, normal) = scene.surface(&intersection);
+
+            // Partially opaque surfaces are skipped with the probability light passes them.
+            if material.opacity < 1. && rng.gen::<f32>() >= material.opacity {
+                ray = offset_ray(point, ray.direction, normal);
+                continue;
+            }
+
+            radiance += throughput * material.emission;
+
+            if material.is_dielectric() {
+                let entering = ray.direction.dot(normal) < 0.;
+                let (facing_normal, eta) = if entering {
+                    (normal, 1. / material.ior)
+                } else {
+                    (-normal, material.ior)
+                };
+                let direction = match ray.direction.refract(facing_normal, eta) {
+                    Some(refracted) => {
+                        let reflectance = fresnel(-ray.direction.dot(facing_normal), refracted.dot(-facing_normal), eta);
+                        if rng.gen::<f32>() < reflectance {
+                            ray.direction.reflect(facing_normal)
+                        } else {
+                            refracted
+                        }
+                    },
+                    None => ray.direction.reflect(facing_normal)
+                };
+                throughput = throughput * material.albedo;
+                ray = offset_ray(point, direction, facing_normal);
+            } else {
+                let facing_normal = if ray.direction.dot(normal) > 0. { -normal } else { normal };
+
+                if rng.gen::<f32>() < material.reflectivity {
+                    throughput = throughput * material.albedo;
+                    ray = offset_ray(point, ray.direction.reflect(facing_normal), facing_normal);
+                } else {
+                    radiance += throughput * direct_lighting(scene, tree, &material, point, facing_normal, -ray.direction);
+                    match sample_brdf(&material, facing_normal, -ray.direction, rng) {
+                        Some((direction, weight)) => {
+                            throughput = throughput * weight;
+                            ray = offset_ray(point, direction, facing_normal);
+                        },
+                        None => break
+                    }
+                }
+            }
+
+            if depth >= self.roulette_depth {
+                let survival = throughput.max_component().min(0.95);
+                if survival <= 0. || rng.gen::<f32>() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        radiance
+    }
+}
+
+/// Reflected contribution of every light that is visible from `point`.
+///
+/// A light's radiance is scaled by π on arrival, so a white diffuse surface
+/// facing a light of intensity 1 reflects exactly 1.
+pub fn direct_lighting(scene: &Scene, tree: &BVH, material: &Material, point: Point, normal: Vector, view_direction: Vector) -> Color {
+    let mut color = Color::black();
+
+    for light in &scene.lights {
+        for sample in light.samples(point) {
+            let product = sample.direction.dot(normal);
+            if product < 0. || sample.radiance.is_black() {
+                continue;
+            }
+
+            let shadow_ray = offset_ray(point, sample.direction, normal);
+            if tree.occluded(shadow_ray, sample.distance - RAY_OFFSET) {
+                continue;
+            }
+
+            color += brdf(material, normal, view_direction, sample.direction) * sample.radiance * (PI * product);
+        }
+    }
+
+    color
+}
+
+/// Lambertian plus energy-normalized Blinn-Phong reflectance from
+/// `light_direction` towards `view_direction`.
+fn brdf(material: &Material, normal: Vector, view_direction: Vector, light_direction: Vector) -> Color {
+    let diffuse = material.albedo / PI;
+    if material.specular <= 0. {
+        return diffuse;
+    }
+    let shininess = material.shininess();
+    let half_vector = (light_direction + view_direction).normalize();
+    let highlight = (shininess + 8.) / (8. * PI) * half_vector.dot(normal).max(0.).powf(shininess);
+    diffuse + Color::white() * (material.specular * highlight)
+}
+
+/// Picks the diffuse or the specular lobe of [`brdf`] in proportion to its
+/// strength and draws a direction from it. Returns the direction together
+/// with the BRDF times the cosine over the probability of that direction, or
+/// `None` if the path is absorbed.
+fn sample_brdf(material: &Material, normal: Vector, view_direction: Vector, rng: &mut impl Rng) -> Option<(Vector, Color)> {
+    let diffuse_weight = material.albedo.max_component();
+    let specular_weight = material.specular.max(0.);
+    if diffuse_weight + specular_weight <= 0. {
+        return None;
+    }
+    let specular_probability = specular_weight / (diffuse_weight + specular_weight);
+    let shininess = material.shininess();
+
+    let direction = if rng.gen::<f32>() < specular_probability {
+        let half_vector = sample_blinn_phong(normal, shininess, rng);
+        (-view_direction).reflect(half_vector)
+    } else {
+        sample_cosine_hemisphere(normal, rng)
+    };
+    let cosine = direction.dot(normal);
+    if cosine <= 0. {
+        return None;
+    }
+
+    let mut pdf = (1. - specular_probability) * cosine / PI;
+    if specular_probability > 0. {
+        let half_vector = (direction + view_direction).normalize();
+        let half_pdf = (shininess + 1.) / (2. * PI) * half_vector.dot(normal).max(0.).powf(shininess);
+        pdf += specular_probability * half_pdf / (4. * direction.dot(half_vector).max(EPSILON));
+    }
+    if pdf <= 0. {
+        return None;
+    }
+    Some((direction, brdf(material, normal, view_direction, direction) * (cosine / pdf)))
+}
+
+/// An orthonormal tangent and bitangent completing `normal`.
+fn tangent_frame(normal: Vector) -> (Vector, Vector) {
+    let helper = if normal.x.abs() > 0.9 { Vector::new(0., 1., 0.) } else { Vector::new(1., 0., 0.) };
+    let tangent = helper.cross(normal).normalize();
+    (tangent, normal.cross(tangent))
+}
+
+/// Draws a Blinn-Phong half vector around `normal`, with density
+/// proportional to the cosine to the power of `shininess`.
+fn sample_blinn_phong(normal: Vector, shininess: f32, rng: &mut impl Rng) -> Vector {
+    let (tangent, bitangent) = tangent_frame(normal);
+    let z = rng.gen::<f32>().powf(1. / (shininess + 1.));
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * z
+}
+
+/// Starts a ray at `point` nudged to the side of the surface it leaves through.
+fn offset_ray(point: Point, direction: Vector, normal: Vector) -> Ray {
+    let side = if direction.dot(normal) < 0. { -normal } else { normal };
+    Ray::new(point + side * RAY_OFFSET, direction)
+}
+
+/// Draws a direction around `normal` with probability proportional to the cosine.
+fn sample_cosine_hemisphere(normal: Vector, rng: &mut impl Rng) -> Vector {
+    let (tangent, bitangent) = tangent_frame(normal);
+
+    let r = rng.gen::<f32>().sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    let z = (1. - r * r).max(0.).sqrt();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * z
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface.
+fn fresnel(cos_i: f32, cos_t: f32, eta: f32) -> f32 {
+    let parallel = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let perpendicular = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.
+}
+
+impl_froms!(Integrator: Whitted, PathTracer);
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{camera::Perspective, sphere::Sphere, plane::Plane, light::{Directional, PointLight}};
+
+    use super::*;
+
+    fn empty_scene() -> Scene {
+        Scene::new(Perspective::new(Point::new(0., 0., 0.), 90., 1., 1).into(), vec![], vec![])
+    }
+
+    #[test]
+    fn test_fresnel() {
+        let normal_incidence = fresnel(1., 1., 1. / 1.5);
+        assert!((normal_incidence - 0.04).abs() < 1e-4);
+        assert_eq!(0., fresnel(1., 1., 1.));
+    }
+
+    #[test]
+    fn test_sample_cosine_hemisphere() {
+        let mut rng = rand::thread_rng();
+        let normal = Vector::new(1., 1., 0.).normalize();
+        for _ in 0..100 {
+            let direction = sample_cosine_hemisphere(normal, &mut rng);
+            assert!(direction.dot(normal) >= 0.);
+            assert!((direction.len() - 1.).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_path_tracer_furnace() {
+        // Inside a closed emissive sphere of radiance 1 with albedo 0.5, the
+        // expected radiance is the geometric series 1 + 0.5 + 0.25 + ... = 2.
+        let mut scene = empty_scene();
+        let id = scene.add_material(Material::new(Color::new(0.5, 0.5, 0.5)).with_emission(Color::white()));
+        scene.add_intersectable(Sphere::new(Point::new(0., 0., 0.), 1.).with_material(id).into());
+        let tree = BVH::new(scene.objects.clone(), scene.meshes.clone(), scene.instances.clone());
+        let path_tracer = PathTracer::new(64);
+
+        let samples = 4000;
+        let mut total = Color::black();
+        for _ in 0..samples {
+            total += path_tracer.radiance(&scene, &tree, Ray::new(Point::new(0., 0., 0.), Vector::new(0., 0., 1.)));
+        }
+        let mean = total / samples as f32;
+        assert!((mean.r - 2.).abs() < 0.1, "{:?}", mean);
+    }
+
+    #[test]
+    fn test_mirror_reflection() {
+        let mut scene = empty_scene();
+        let mirror = scene.add_material(Material::new(Color::white()).with_reflectivity(1.));
+        let emitter = scene.add_material(Material::new(Color::black()).with_emission(Color::new(1., 0., 0.)));
+        scene.add_intersectable(Plane::new(Vector::new(0., 1., 0.), Point::new(0., -1., 0.)).with_material(mirror).into());
+        scene.add_intersectable(Sphere::new(Point::new(0., 3., 0.), 1.).with_material(emitter).into());
+        scene.add_light(Directional::new(Vector::new(0., -1., 0.), 1.).into());
+        let tree = BVH::new(scene.objects.clone(), scene.meshes.clone(), scene.instances.clone());
+        let ray = Ray::new(Point::new(0., 0., 0.), Vector::new(0., -1., 0.));
+
+        assert_eq!(Color::black(), Whitted::new(0).radiance(&scene, &tree, ray));
+        assert_eq!(Color::new(1., 0., 0.), Whitted::new(1).radiance(&scene, &tree, ray));
+    }
+
+    #[test]
+    fn test_refraction() {
+        let mut scene = empty_scene();
+        let glass = scene.add_material(Material::new(Color::white()).with_ior(1.5));
+        let emitter = scene.add_material(Material::new(Color::black()).with_emission(Color::new(1., 1., 1.)));
+        scene.add_intersectable(Sphere::new(Point::new(0., 0., -3.), 1.).with_material(glass).into());
+        scene.add_intersectable(Sphere::new(Point::new(0., 0., -10.), 1.).with_material(emitter).into());
+        let tree = BVH::new(scene.objects.clone(), scene.meshes.clone(), scene.instances.clone());
+        let ray = Ray::new(Point::new(0., 0., 0.), Vector::new(0., 0., -1.));
+
+        let transmitted = Whitted::new(5).radiance(&scene, &tree, ray);
+        // Two interfaces at normal incidence transmit 96% each, plus one internal bounce pair.
+        let expected = 0.96 * 0.96 * (1. + 0.04 * 0.04);
+        assert!((transmitted.r - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_light_sampling_matches_bounces() {
+        // A diffuse plane lit by a point light must look the same as when it
+        // is lit only through bounces by an emissive sphere of the same power.
+        let (radius, height, emission) = (1., 3., 2.);
+        let ray = Ray::new(Point::new(3., 1., 0.), Vector::new(-3., -1., 0.));
+
+        let mut lit = empty_scene();
+        let diffuse = lit.add_material(Material::new(Color::new(0.5, 0.5, 0.5)));
+        lit.add_intersectable(Plane::new(Vector::new(0., 1., 0.), Point::new(0., 0., 0.)).with_material(diffuse).into());
+        lit.add_light(PointLight::new(Point::new(0., height, 0.), emission * radius * radius).into());
+        let tree = BVH::new(lit.objects.clone(), lit.meshes.clone(), lit.instances.clone());
+        let direct = PathTracer::new(0).radiance(&lit, &tree, ray);
+
+        let mut bounced = empty_scene();
+        let diffuse = bounced.add_material(Material::new(Color::new(0.5, 0.5, 0.5)));
+        let emitter = bounced.add_material(Material::new(Color::black()).with_emission(Color::white() * emission));
+        bounced.add_intersectable(Plane::new(Vector::new(0., 1., 0.), Point::new(0., 0., 0.)).with_material(diffuse).into());
+        bounced.add_intersectable(Sphere::new(Point::new(0., height, 0.), radius).with_material(emitter).into());
+        let tree = BVH::new(bounced.objects.clone(), bounced.meshes.clone(), bounced.instances.clone());
+        let mut rng = StdRng::seed_from_u64(5);
+        let samples = 50000;
+        let mut total = Color::black();
+        for _ in 0..samples {
+            total += PathTracer::new(1).trace(&bounced, &tree, ray, &mut rng);
+        }
+        let indirect = total / samples as f32;
+
+        // Albedo times emission times the sine squared of the sphere's angular radius.
+        let expected = 0.5 * emission * (radius / height) * (radius / height);
+        assert!((direct.r - expected).abs() < 1e-5, "{:?}", direct);
+        assert!((indirect.r - expected).abs() < 0.03 * expected, "{:?}", indirect);
+    }
+
+    #[test]
+    fn test_sample_brdf() {
+        // Averaging the sampled weights integrates the BRDF times the cosine,
+        // which for a diffuse surface is its albedo.
+        let mut rng = StdRng::seed_from_u64(1);
+        let normal = Vector::new(0., 1., 0.);
+        let view = Vector::new(1., 1., 0.).normalize();
+        let diffuse = Material::new(Color::new(0.5, 0.5, 0.5));
+        let (_, weight) = sample_brdf(&diffuse, normal, view, &mut rng).unwrap();
+        assert!((weight.r - 0.5).abs() < 1e-5);
+
+        let glossy = diffuse.with_specular(0.3, 0.3);
+        let samples = 20000;
+        let mut total = Color::black();
+        for _ in 0..samples {
+            if let Some((direction, weight)) = sample_brdf(&glossy, normal, view, &mut rng) {
+                assert!(direction.dot(normal) > 0.);
+                total += weight;
+            }
+        }
+        let mean = total / samples as f32;
+        assert!(mean.r > 0.5 && mean.r < 0.85, "{:?}", mean);
+    }
+}
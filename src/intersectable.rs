@@ -1,4 +1,4 @@
-use crate::{ray::Ray, sphere::Sphere, plane::Plane, intersection::Intersection, impl_froms, point::Point, vector::Vector, triangle::Triangle, aabb::{Bounded, AABB}};
+use crate::{color::Color, ray::Ray, sphere::Sphere, plane::Plane, intersection::Intersection, impl_froms, point::Point, vector::Vector, triangle::Triangle, aabb::{Bounded, AABB}};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Intersectable {
@@ -23,6 +23,47 @@ impl Intersectable {
             Intersectable::Triangle(triangle) => triangle.normal_at_point(point)
         }
     }
+
+    /// Shading normal at a hit; triangles interpolate their vertex normals
+    /// with the barycentric coordinates of the hit.
+    pub fn normal_at(self, point: Point, barycentric: (f32, f32)) -> Vector {
+        match self {
+            Intersectable::Triangle(triangle) => triangle.normal_at(barycentric.0, barycentric.1),
+            _ => self.normal_at_point(point)
+        }
+    }
+
+    /// Texture coordinates at a hit, for surfaces that carry them.
+    pub fn uv_at(self, barycentric: (f32, f32)) -> Option<(f32, f32)> {
+        match self {
+            Intersectable::Triangle(triangle) => triangle.uv_at(barycentric.0, barycentric.1),
+            _ => None
+        }
+    }
+
+    /// Interpolated vertex color at a hit, for surfaces that carry them.
+    pub fn color_at(self, barycentric: (f32, f32)) -> Option<Color> {
+        match self {
+            Intersectable::Triangle(triangle) => triangle.color_at(barycentric.0, barycentric.1),
+            _ => None
+        }
+    }
+
+    /// Derivatives of the surface position with respect to `u` and `v`.
+    pub fn uv_tangents(self) -> Option<(Vector, Vector)> {
+        match self {
+            Intersectable::Triangle(triangle) => triangle.uv_tangents(),
+            _ => None
+        }
+    }
+
+    pub fn material(self) -> usize {
+        match self {
+            Intersectable::Sphere(sphere) => sphere.material,
+            Intersectable::Plane(plane) => plane.material,
+            Intersectable::Triangle(triangle) => triangle.material
+        }
+    }
 }
 
 impl Bounded for Intersectable {
@@ -1,8 +1,28 @@
-use crate::{intersectable::Intersectable, point::Point};
+use crate::{color::Color, intersectable::Intersectable, point::Point, vector::Vector};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Intersection {
     pub t: f32,
     pub point: Point,
     pub object: Intersectable,
-}
\ No newline at end of file
+    /// Barycentric coordinates `(u, v)` of the hit on a triangle, the weights
+    /// of its second and third vertex. Zero for other surfaces.
+    pub barycentric: (f32, f32),
+}
+
+impl Intersection {
+    /// Shading normal at the hit, interpolated from vertex normals on triangles.
+    pub fn normal(&self) -> Vector {
+        self.object.normal_at(self.point, self.barycentric)
+    }
+
+    /// Texture coordinates at the hit, for surfaces that carry them.
+    pub fn uv(&self) -> Option<(f32, f32)> {
+        self.object.uv_at(self.barycentric)
+    }
+
+    /// Interpolated vertex color at the hit, for surfaces that carry them.
+    pub fn color(&self) -> Option<Color> {
+        self.object.color_at(self.barycentric)
+    }
+}
@@ -16,5 +16,17 @@ pub mod mesh;
 pub mod matrix;
 pub mod aabb;
 pub mod bvh;
+pub mod instance;
+pub mod material;
+pub mod integrator;
+pub mod sampler;
+pub mod filter;
+pub mod scene_file;
+pub mod obj;
+pub mod mtl;
+pub mod texture;
+pub mod stl;
+pub mod ply;
+pub mod gltf;
 
 pub const EPSILON: f32 = 1e-6;
\ No newline at end of file
@@ -1,13 +1,280 @@
-use crate::{vector::Vector, impl_froms};
+use rand::Rng;
+
+use crate::{vector::Vector, point::Point, color::Color, impl_froms};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Light {
-    Directional(Directional)
+    Directional(Directional),
+    Point(PointLight),
+    Spot(SpotLight),
+    Area(AreaLight)
+}
+
+/// Direction towards a light as seen from a shaded point, together with the
+/// distance a shadow ray has to travel and the radiance arriving along it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightSample {
+    pub direction: Vector,
+    pub distance: f32,
+    pub radiance: Color
+}
+
+impl Light {
+    /// Picks a single sample of the light as seen from `point`.
+    pub fn sample(self, point: Point) -> LightSample {
+        match self {
+            Light::Directional(light) => light.sample(point),
+            Light::Point(light) => light.sample(point),
+            Light::Spot(light) => light.sample(point),
+            Light::Area(light) => light.sample(point)
+        }
+    }
+
+    /// All samples needed to estimate the light at `point`, with radiance
+    /// already weighted so they can simply be summed.
+    pub fn samples(self, point: Point) -> Vec<LightSample> {
+        match self {
+            Light::Area(light) => light.samples(point),
+            _ => vec![self.sample(point)]
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Directional {
-    pub direction: Vector
+    pub direction: Vector,
+    pub color: Color,
+    pub intensity: f32
+}
+
+impl Directional {
+    pub fn new(direction: Vector, intensity: f32) -> Directional {
+        Directional { direction: direction.normalize(), color: Color::white(), intensity }
+    }
+
+    pub fn with_color(self, color: Color) -> Directional {
+        Directional { color, ..self }
+    }
+
+    pub fn sample(self, _point: Point) -> LightSample {
+        LightSample {
+            direction: -self.direction.normalize(),
+            distance: f32::INFINITY,
+            radiance: self.color * self.intensity
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Point,
+    pub color: Color,
+    pub intensity: f32,
+    /// Exponent of the distance attenuation: 0 disables it, 2 is physically correct.
+    pub falloff: f32
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: f32) -> PointLight {
+        PointLight { position, color: Color::white(), intensity, falloff: 2. }
+    }
+
+    pub fn with_color(self, color: Color) -> PointLight {
+        PointLight { color, ..self }
+    }
+
+    pub fn with_falloff(self, falloff: f32) -> PointLight {
+        PointLight { falloff, ..self }
+    }
+
+    pub fn sample(self, point: Point) -> LightSample {
+        let to_light = self.position - point;
+        let distance = to_light.len();
+        LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.color * (self.intensity / distance.powf(self.falloff))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: Point,
+    pub direction: Vector,
+    pub color: Color,
+    pub intensity: f32,
+    pub falloff: f32,
+    /// Half-angle in radians inside which the spot is at full intensity.
+    pub inner_angle: f32,
+    /// Half-angle in radians outside which the spot contributes nothing.
+    pub outer_angle: f32
+}
+
+impl SpotLight {
+    pub fn new(position: Point, direction: Vector, intensity: f32, inner_angle: f32, outer_angle: f32) -> SpotLight {
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            color: Color::white(),
+            intensity,
+            falloff: 2.,
+            inner_angle,
+            outer_angle: outer_angle.max(inner_angle)
+        }
+    }
+
+    pub fn with_falloff(self, falloff: f32) -> SpotLight {
+        SpotLight { falloff, ..self }
+    }
+
+    pub fn with_color(self, color: Color) -> SpotLight {
+        SpotLight { color, ..self }
+    }
+
+    fn cone_attenuation(self, direction_from_light: Vector) -> f32 {
+        let cos_angle = direction_from_light.dot(self.direction);
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.cos();
+        if cos_angle >= cos_inner {
+            1.
+        } else if cos_angle <= cos_outer {
+            0.
+        } else {
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3. - 2. * t)
+        }
+    }
+
+    pub fn sample(self, point: Point) -> LightSample {
+        let to_light = self.position - point;
+        let distance = to_light.len();
+        let direction = to_light / distance;
+        LightSample {
+            direction,
+            distance,
+            radiance: self.color * (self.intensity * self.cone_attenuation(-direction) / distance.powf(self.falloff))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaShape {
+    /// Parallelogram spanned by two edges from a corner; emits from both sides.
+    Rectangle { corner: Point, edge1: Vector, edge2: Vector },
+    Sphere { center: Point, radius: f32 }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AreaLight {
+    pub shape: AreaShape,
+    pub color: Color,
+    pub intensity: f32,
+    /// Number of shadow rays used per shaded point; more gives smoother penumbrae.
+    pub samples: u32
+}
+
+impl AreaLight {
+    pub fn rectangle(corner: Point, edge1: Vector, edge2: Vector, intensity: f32) -> AreaLight {
+        AreaLight { shape: AreaShape::Rectangle { corner, edge1, edge2 }, color: Color::white(), intensity, samples: 16 }
+    }
+
+    pub fn sphere(center: Point, radius: f32, intensity: f32) -> AreaLight {
+        AreaLight { shape: AreaShape::Sphere { center, radius }, color: Color::white(), intensity, samples: 16 }
+    }
+
+    pub fn with_samples(self, samples: u32) -> AreaLight {
+        AreaLight { samples: samples.max(1), ..self }
+    }
+
+    pub fn with_color(self, color: Color) -> AreaLight {
+        AreaLight { color, ..self }
+    }
+
+    fn sample_position(self, point: Point, rng: &mut impl Rng) -> (Point, Vector) {
+        match self.shape {
+            AreaShape::Rectangle { corner, edge1, edge2 } => {
+                let position = corner + edge1 * rng.gen::<f32>() + edge2 * rng.gen::<f32>();
+                (position, edge1.cross(edge2).normalize())
+            },
+            AreaShape::Sphere { center, radius } => {
+                let z = 1. - 2. * rng.gen::<f32>();
+                let r = (1. - z * z).max(0.).sqrt();
+                let phi = 2. * std::f32::consts::PI * rng.gen::<f32>();
+                let mut normal = Vector::new(r * phi.cos(), r * phi.sin(), z);
+                if normal.dot(point - center) < 0. {
+                    normal = -normal;
+                }
+                (center + normal * radius, normal)
+            }
+        }
+    }
+
+    pub fn sample(self, point: Point) -> LightSample {
+        let (position, normal) = self.sample_position(point, &mut rand::thread_rng());
+        let to_light = position - point;
+        let distance = to_light.len();
+        let direction = to_light / distance;
+        LightSample {
+            direction,
+            distance,
+            radiance: self.color * (self.intensity * direction.dot(normal).abs() / (distance * distance))
+        }
+    }
+
+    pub fn samples(self, point: Point) -> Vec<LightSample> {
+        (0..self.samples)
+            .map(|_| {
+                let mut sample = self.sample(point);
+                sample.radiance *= 1. / self.samples as f32;
+                sample
+            })
+            .collect()
+    }
 }
 
-impl_froms!(Light: Directional);
\ No newline at end of file
+impl_froms!(Light: Directional(Directional), Point(PointLight), Spot(SpotLight), Area(AreaLight));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directional_sample() {
+        let light = Directional::new(Vector::new(0., -2., 0.), 2.).with_color(Color::new(1., 0.5, 0.));
+        let sample = Light::from(light).sample(Point::new(1., 2., 3.));
+        assert_eq!(Vector::new(0., 1., 0.), sample.direction);
+        assert_eq!(f32::INFINITY, sample.distance);
+        assert_eq!(Color::new(2., 1., 0.), sample.radiance);
+    }
+
+    #[test]
+    fn test_point_sample() {
+        let light = PointLight::new(Point::new(0., 4., 0.), 32.);
+        let sample = light.sample(Point::new(0., 0., 0.));
+        assert_eq!(Vector::new(0., 1., 0.), sample.direction);
+        assert_eq!(4., sample.distance);
+        assert_eq!(Color::new(2., 2., 2.), sample.radiance);
+        assert_eq!(Color::new(32., 32., 32.), light.with_falloff(0.).sample(Point::new(0., 0., 0.)).radiance);
+    }
+
+    #[test]
+    fn test_spot_cone() {
+        let light = SpotLight::new(Point::new(0., 1., 0.), Vector::new(0., -1., 0.), 1., 0.2, 0.4);
+        assert_eq!(Color::white(), light.sample(Point::new(0., 0., 0.)).radiance);
+        assert_eq!(Color::black(), light.sample(Point::new(1., 0., 0.)).radiance);
+        let edge = light.sample(Point::new(0.3f32.tan(), 0., 0.)).radiance.r;
+        assert!(edge > 0. && edge < 1.);
+    }
+
+    #[test]
+    fn test_area_samples() {
+        let light = AreaLight::rectangle(Point::new(-1., 2., -1.), Vector::new(2., 0., 0.), Vector::new(0., 0., 2.), 4.).with_samples(8);
+        let samples = light.samples(Point::new(0., 0., 0.));
+        assert_eq!(8, samples.len());
+        for sample in samples {
+            assert!(sample.direction.y > 0.);
+            assert!(sample.distance >= 2. && sample.distance <= 6f32.sqrt());
+        }
+    }
+}
@@ -8,6 +8,15 @@ macro_rules! impl_froms {
                 }
             }
         )*
+    };
+    ($n:ident: $($v:ident($x:ty)),*) => {
+        $(
+            impl From<$x> for $n {
+                fn from(x: $x) -> $n {
+                    $n::$v(x)
+                }
+            }
+        )*
     }
 }
 
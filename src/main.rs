@@ -1,32 +1,147 @@
-use graphics_engine::{camera::Camera, point::Point, scene::Scene, vector::Vector, light::{Directional}, renderer::{Renderer, Png}, mesh::Mesh, matrix::Matrix, sphere::Sphere};
-use clap::Parser;
+use graphics_engine::{camera::Perspective, point::Point, scene::Scene, vector::Vector, light::{Directional}, renderer::{Renderer, Png, RenderSettings}, integrator::{Integrator, Whitted, PathTracer}, scene_file, gltf, sampler::Sampler, filter::Filter, mesh::Mesh, matrix::Matrix, sphere::Sphere, material::Material, color::Color, obj::ObjError};
+use clap::{Parser, ArgEnum};
 
-const WIDTH: u32 = 600;
-const HEIGHT: u32 = 600;
+const SIZE: u32 = 600;
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum IntegratorKind {
+    Whitted,
+    Path,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum SamplerKind {
+    Random,
+    Stratified,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
+    /// Scene description file or glTF scene (.gltf, .glb); when omitted the built-in scene around --source is rendered
+    #[clap(long)]
+    scene: Option<String>,
+
     #[clap(long, default_value = "k.obj")]
     source: String,
 
     #[clap(long, default_value = "test.png")]
     output: String,
+
+    /// Image width in pixels for glTF and built-in scenes; glTF scenes default to their camera's aspect ratio
+    #[clap(long)]
+    width: Option<u32>,
+
+    /// Image height in pixels for glTF and built-in scenes
+    #[clap(long)]
+    height: Option<u32>,
+
+    #[clap(long, arg_enum)]
+    integrator: Option<IntegratorKind>,
+
+    /// Maximum number of bounces traced per camera ray
+    #[clap(long)]
+    max_depth: Option<u32>,
+
+    /// Samples per pixel
+    #[clap(long)]
+    spp: Option<u32>,
+
+    #[clap(long, arg_enum)]
+    sampler: Option<SamplerKind>,
+
+    /// Pixel reconstruction filter
+    #[clap(long, arg_enum)]
+    filter: Option<FilterKind>,
 }
 
-fn main() {
-    let args = Args::parse();
-   
-    let mut scene = Scene::new(Camera::new(Point::new(0., 0., 1.5), 70., WIDTH as f32 / HEIGHT as f32, HEIGHT), vec![], vec![]);
+fn default_scene(source: &str, width: u32, height: u32) -> Result<Scene, ObjError> {
+    let mut scene = Scene::new(Perspective::new(Point::new(0., 0., 1.5), 70., width as f32 / height as f32, height).into(), vec![], vec![]);
 
-    scene.add_intersectable(Sphere::new(Point::new(-0.5, 0., 0.7), 0.2).apply_transform(&Matrix::scale(0.5, 0.5, 0.5)).apply_transform(&Matrix::translate(-0.3, 0.2, 0.)).into());
-    let mesh = Mesh::from_model(args.source.as_str()).unwrap();
+    let red = scene.add_material(Material::new(Color::new(0.6, 0.05, 0.05)).with_specular(0.5, 0.3));
+
+    scene.add_intersectable(Sphere::new(Point::new(-0.5, 0., 0.7), 0.2).with_material(red).apply_transform(&Matrix::translate(-0.3, 0.2, 0.).multiply(&Matrix::scale(0.5, 0.5, 0.5))).unwrap().into());
+    let mesh = Mesh::from_model(source)?;
     // let transformed_mesh = mesh.apply_transform(&Matrix::rotate_x(-std::f32::consts::FRAC_PI_2)).apply_transform(&Matrix::rotate_y(-std::f32::consts::FRAC_PI_4));
     scene.add_mesh(mesh.apply_transform(&Matrix::rotate_x(-std::f32::consts::FRAC_PI_2)).apply_transform(&Matrix::rotate_y(-std::f32::consts::FRAC_PI_4)).apply_transform(&Matrix::translate(0.1, -0.3, -0.1)));
-    scene.add_light(Directional { direction: Vector::new(-1., -1., -1.).normalize() }.into());
-    scene.add_light(Directional { direction: Vector::new(1., -1., -1.).normalize() }.into());
-    scene.add_light(Directional { direction: Vector::new(0., 0., -1.).normalize() }.into());
+    scene.add_light(Directional::new(Vector::new(-1., -1., -1.), 1.).into());
+    scene.add_light(Directional::new(Vector::new(1., -1., -1.), 1.).into());
+    scene.add_light(Directional::new(Vector::new(0., 0., -1.), 1.).into());
+
+    Ok(scene)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let height = args.height.unwrap_or(SIZE);
+
+    let (scene, width, height, mut settings) = match &args.scene {
+        Some(path) if path.ends_with(".gltf") || path.ends_with(".glb") => match gltf::load(path, args.width, height) {
+            Ok(file) => (file.scene, file.width, file.height, RenderSettings::default()),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        Some(path) => match scene_file::load(path) {
+            Ok(file) => (file.scene, file.width, file.height, file.settings),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            }
+        },
+        None => match default_scene(&args.source, args.width.unwrap_or(SIZE), height) {
+            Ok(scene) => (scene, args.width.unwrap_or(SIZE), height, RenderSettings::default()),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    if args.integrator.is_some() || args.max_depth.is_some() {
+        let (kind, max_depth) = match settings.integrator {
+            Integrator::Whitted(whitted) => (IntegratorKind::Whitted, whitted.max_depth),
+            Integrator::PathTracer(path_tracer) => (IntegratorKind::Path, path_tracer.max_depth),
+        };
+        let max_depth = args.max_depth.unwrap_or(max_depth);
+        settings.integrator = match args.integrator.unwrap_or(kind) {
+            IntegratorKind::Whitted => Whitted::new(max_depth).into(),
+            IntegratorKind::Path => PathTracer::new(max_depth).into(),
+        };
+    }
+
+    if let Some(spp) = args.spp {
+        settings.samples_per_pixel = spp;
+    }
+
+    if let Some(sampler) = args.sampler {
+        settings.sampler = match sampler {
+            SamplerKind::Random => Sampler::Random,
+            SamplerKind::Stratified => Sampler::Stratified,
+        };
+    }
+
+    if let Some(filter) = args.filter {
+        settings.filter = match filter {
+            FilterKind::Box => Filter::Box,
+            FilterKind::Tent => Filter::Tent,
+            FilterKind::Gaussian => Filter::Gaussian,
+            FilterKind::Mitchell => Filter::Mitchell,
+        };
+    }
 
-    let png_renderer: Renderer = Png::new(&scene, args.output, WIDTH, HEIGHT).into();
+    let png_renderer: Renderer = Png::new(&scene, args.output, width, height)
+        .with_settings(settings)
+        .into();
     png_renderer.render();
 }
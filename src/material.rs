@@ -0,0 +1,130 @@
+use crate::color::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub albedo: Color,
+    pub specular: f32,
+    pub roughness: f32,
+    pub emission: Color,
+    pub reflectivity: f32,
+    pub ior: f32,
+    /// Fraction of light stopped by the surface; the rest passes straight through.
+    pub opacity: f32,
+    /// Index into `Scene::textures` that replaces `albedo` where it is mapped.
+    pub albedo_texture: Option<usize>,
+    /// Index into `Scene::textures` that multiplies `emission` where it is mapped.
+    pub emission_texture: Option<usize>,
+    /// Index into `Scene::textures` of a height map perturbing the shading normal.
+    pub bump_texture: Option<usize>,
+    pub bump_scale: f32
+}
+
+impl Material {
+    pub fn new(albedo: Color) -> Material {
+        Material { albedo, ..Material::default() }
+    }
+
+    pub fn with_specular(self, specular: f32, roughness: f32) -> Material {
+        Material { specular, roughness, ..self }
+    }
+
+    pub fn with_emission(self, emission: Color) -> Material {
+        Material { emission, ..self }
+    }
+
+    pub fn with_reflectivity(self, reflectivity: f32) -> Material {
+        Material { reflectivity, ..self }
+    }
+
+    pub fn with_ior(self, ior: f32) -> Material {
+        Material { ior, ..self }
+    }
+
+    pub fn with_opacity(self, opacity: f32) -> Material {
+        Material { opacity: opacity.clamp(0., 1.), ..self }
+    }
+
+    pub fn with_albedo_texture(self, texture: usize) -> Material {
+        Material { albedo_texture: Some(texture), ..self }
+    }
+
+    pub fn with_emission_texture(self, texture: usize) -> Material {
+        Material { emission_texture: Some(texture), ..self }
+    }
+
+    pub fn with_bump_texture(self, texture: usize, scale: f32) -> Material {
+        Material { bump_texture: Some(texture), bump_scale: scale, ..self }
+    }
+
+    /// Materials with an index of refraction above 1 are treated as transparent
+    /// dielectrics such as glass or water.
+    pub fn is_dielectric(self) -> bool {
+        self.ior > 1.
+    }
+
+    /// Blinn-Phong exponent matching the roughness of the material.
+    pub fn shininess(self) -> f32 {
+        let roughness = self.roughness.clamp(0.01, 1.);
+        (2. / (roughness * roughness) - 2.).max(1.)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            albedo: Color::white(),
+            specular: 0.,
+            roughness: 1.,
+            emission: Color::black(),
+            reflectivity: 0.,
+            ior: 1.,
+            opacity: 1.,
+            albedo_texture: None,
+            emission_texture: None,
+            bump_texture: None,
+            bump_scale: 1.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let albedo = Color::new(1., 0., 0.);
+        let material = Material::new(albedo);
+        assert_eq!(albedo, material.albedo);
+        assert_eq!(0., material.specular);
+        assert_eq!(Color::black(), material.emission);
+        assert_eq!(1., material.ior);
+    }
+
+    #[test]
+    fn test_builders() {
+        let material = Material::new(Color::new(0.1, 0.2, 0.3))
+            .with_specular(0.5, 0.2)
+            .with_emission(Color::new(1., 2., 3.))
+            .with_reflectivity(0.7)
+            .with_ior(1.5)
+            .with_opacity(1.5)
+            .with_albedo_texture(2)
+            .with_bump_texture(3, 0.5);
+        assert_eq!(0.5, material.specular);
+        assert_eq!(0.2, material.roughness);
+        assert_eq!(Color::new(1., 2., 3.), material.emission);
+        assert_eq!(0.7, material.reflectivity);
+        assert_eq!(1.5, material.ior);
+        assert_eq!(1., material.opacity);
+        assert_eq!(Some(2), material.albedo_texture);
+        assert_eq!(Some(3), material.bump_texture);
+        assert_eq!(0.5, material.bump_scale);
+    }
+
+    #[test]
+    fn test_shininess() {
+        assert_eq!(1., Material::default().shininess());
+        assert_eq!(6., Material::default().with_specular(1., 0.5).shininess());
+    }
+}
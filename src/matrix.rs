@@ -2,7 +2,7 @@ use std::{fmt, sync::Arc};
 
 use crossbeam::atomic::AtomicCell;
 
-use crate::m;
+use crate::{m, point::Point, vector::Vector};
 
 #[derive(Debug, Clone)]
 pub struct Matrix {
@@ -87,6 +87,140 @@ impl Matrix {
         ]
     }
 
+    pub fn transpose(&self) -> Matrix {
+        let mut result = Matrix::new(self.cols, self.rows, vec![0.; self.rows * self.cols]);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                result.set(j, i, self.get(i, j));
+            }
+        }
+        result
+    }
+
+    /// Determinant of a square matrix, by Gaussian elimination with partial pivoting.
+    pub fn determinant(&self) -> f32 {
+        assert_eq!(self.rows, self.cols);
+
+        let mut m = self.clone();
+        let mut determinant = 1.;
+        for column in 0..m.cols {
+            let pivot = (column..m.rows)
+                .max_by(|&a, &b| m.get(a, column).abs().total_cmp(&m.get(b, column).abs()))
+                .unwrap();
+            if m.get(pivot, column) == 0. {
+                return 0.;
+            }
+            if pivot != column {
+                m.swap_rows(pivot, column);
+                determinant = -determinant;
+            }
+            determinant *= m.get(column, column);
+            for row in column + 1..m.rows {
+                let factor = m.get(row, column) / m.get(column, column);
+                for j in column..m.cols {
+                    m.set(row, j, m.get(row, j) - factor * m.get(column, j));
+                }
+            }
+        }
+        determinant
+    }
+
+    /// Inverse of a square matrix by Gauss-Jordan elimination, or `None`
+    /// when it is singular.
+    pub fn inverse(&self) -> Option<Matrix> {
+        assert_eq!(self.rows, self.cols);
+
+        let n = self.rows;
+        let mut m = self.clone();
+        let mut inverse = Matrix::new(n, n, vec![0.; n * n]);
+        for i in 0..n {
+            inverse.set(i, i, 1.);
+        }
+        // Pivots this small relative to the entries mean the rows are dependent.
+        // The translation of an affine transform never takes part in choosing
+        // a pivot, so only its linear block sets the scale.
+        let affine = n == 4 && (0..3).all(|j| self.get(3, j) == 0.) && self.get(3, 3) == 1.;
+        let linear = if affine { 3 } else { n };
+        let largest = (0..linear)
+            .flat_map(|i| (0..linear).map(move |j| (i, j)))
+            .fold(0f32, |largest, (i, j)| largest.max(self.get(i, j).abs()));
+        let tolerance = largest * n as f32 * f32::EPSILON;
+
+        for column in 0..n {
+            let pivot = (column..n)
+                .max_by(|&a, &b| m.get(a, column).abs().total_cmp(&m.get(b, column).abs()))
+                .unwrap();
+            if m.get(pivot, column).abs() <= tolerance {
+                return None;
+            }
+            m.swap_rows(pivot, column);
+            inverse.swap_rows(pivot, column);
+
+            let scale = 1. / m.get(column, column);
+            for j in 0..n {
+                m.set(column, j, m.get(column, j) * scale);
+                inverse.set(column, j, inverse.get(column, j) * scale);
+            }
+            for row in (0..n).filter(|&row| row != column) {
+                let factor = m.get(row, column);
+                for j in 0..n {
+                    m.set(row, j, m.get(row, j) - factor * m.get(column, j));
+                    inverse.set(row, j, inverse.get(row, j) - factor * inverse.get(column, j));
+                }
+            }
+        }
+        Some(inverse)
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        for j in 0..self.cols {
+            self.data.swap(a * self.cols + j, b * self.cols + j);
+        }
+    }
+
+    /// The factor by which an affine transform scales lengths in every
+    /// direction, or `None` if it stretches some directions more than others.
+    pub fn uniform_scale(&self) -> Option<f32> {
+        let column = |j: usize| Vector::new(self.get(0, j), self.get(1, j), self.get(2, j));
+        let columns = [column(0), column(1), column(2)];
+        let scale = self.determinant().abs().cbrt();
+        let tolerance = 1e-4 * scale * scale;
+        let even = (0..3).all(|i| {
+            (columns[i].len_sq() - scale * scale).abs() <= tolerance
+                && columns[i].dot(columns[(i + 1) % 3]).abs() <= tolerance
+        });
+        if even { Some(scale) } else { None }
+    }
+
+    /// Matrix that transforms surface normals: the inverse transpose, which
+    /// keeps normals perpendicular to surfaces under non-uniform scaling.
+    /// Singular transforms flatten surfaces and keep their own matrix.
+    pub fn normal_matrix(&self) -> Matrix {
+        match self.inverse() {
+            Some(inverse) => inverse.transpose(),
+            None => self.clone()
+        }
+    }
+
+    /// Applies an affine 4x4 transform to a point, including its translation.
+    pub fn transform_point(&self, point: Point) -> Point {
+        let row = |i: usize| self.get(i, 0) * point.x + self.get(i, 1) * point.y + self.get(i, 2) * point.z + self.get(i, 3);
+        Point::new(row(0), row(1), row(2))
+    }
+
+    /// Applies an affine 4x4 transform to a direction, ignoring its translation.
+    pub fn transform_vector(&self, vector: Vector) -> Vector {
+        let row = |i: usize| self.get(i, 0) * vector.x + self.get(i, 1) * vector.y + self.get(i, 2) * vector.z;
+        Vector::new(row(0), row(1), row(2))
+    }
+
+    /// Transforms a normal with the inverse transpose and normalizes it. When
+    /// transforming many normals, compute [`Matrix::normal_matrix`] once and
+    /// use [`Matrix::transform_vector`] instead.
+    pub fn transform_normal(&self, normal: Vector) -> Vector {
+        self.normal_matrix().transform_vector(normal).normalize()
+    }
+
     pub fn multiply(&self, other: &Matrix) -> Matrix {
         assert_eq!(self.cols, other.rows);
 
@@ -121,8 +255,73 @@ impl fmt::Display for Matrix {
             for j in 0..self.cols {
                 write!(f, "{} ", self.data[i * self.cols + j])?;
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: &Matrix, actual: &Matrix) {
+        assert_eq!((expected.rows, expected.cols), (actual.rows, actual.cols));
+        for (a, b) in expected.data.iter().zip(&actual.data) {
+            assert!((a - b).abs() < 1e-5, "expected\n{}got\n{}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_transpose() {
+        let matrix = m![1., 2., 3.; 4., 5., 6.];
+        let transposed = matrix.transpose();
+        assert_eq!((3, 2), (transposed.rows, transposed.cols));
+        assert_eq!(vec![1., 4., 2., 5., 3., 6.], transposed.data);
+    }
+
+    #[test]
+    fn test_determinant() {
+        assert_eq!(24., Matrix::scale(2., 3., 4.).determinant());
+        assert!((Matrix::rotate_y(0.7).multiply(&Matrix::translate(1., 2., 3.)).determinant() - 1.).abs() < 1e-6);
+        assert_eq!(-1., Matrix::scale(-1., 1., 1.).determinant());
+        // Needs a row swap to find a pivot.
+        assert_eq!(-2., m![0., 1.; 2., 0.].determinant());
+        assert_eq!(0., m![1., 2.; 2., 4.].determinant());
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Matrix::translate(1., -2., 3.)
+            .multiply(&Matrix::rotate_x(0.3))
+            .multiply(&Matrix::scale(2., 0.5, 4.));
+        let inverse = transform.inverse().unwrap();
+        assert_close(&Matrix::identity(), &transform.multiply(&inverse));
+        assert_close(&Matrix::identity(), &inverse.multiply(&transform));
+        assert_close(&Matrix::translate(-1., 2., -3.), &Matrix::translate(1., -2., 3.).inverse().unwrap());
+        assert!(Matrix::scale(1., 0., 1.).inverse().is_none());
+
+        // A far translation does not make a small scale look singular.
+        let far = Matrix::translate(1e6, 0., 0.).multiply(&Matrix::scale(0.01, 0.01, 0.01));
+        let inverse = far.inverse().unwrap();
+        assert!((inverse.transform_point(Point::new(1e6, 0., 0.)) - Point::new(0., 0., 0.)).len() < 1e-3);
+    }
+
+    #[test]
+    fn test_uniform_scale() {
+        let transform = Matrix::translate(1., 2., 3.).multiply(&Matrix::rotate_y(0.7)).multiply(&Matrix::scale(-2., 2., 2.));
+        assert!((transform.uniform_scale().unwrap() - 2.).abs() < 1e-5);
+        assert_eq!(None, Matrix::rotate_x(0.3).multiply(&Matrix::scale(1., 2., 1.)).uniform_scale());
+        assert_eq!(None, Matrix::scale(1., 0., 1.).uniform_scale());
+    }
+
+    #[test]
+    fn test_transform() {
+        let transform = Matrix::translate(1., 2., 3.).multiply(&Matrix::scale(2., 1., 1.));
+        assert_eq!(Point::new(3., 3., 4.), transform.transform_point(Point::new(1., 1., 1.)));
+        assert_eq!(Vector::new(2., 1., 1.), transform.transform_vector(Vector::new(1., 1., 1.)));
+        // Stretching along x tilts normals of slanted surfaces away from x.
+        let normal = transform.transform_normal(Vector::new(1., 1., 0.));
+        assert!((normal - Vector::new(1., 2., 0.).normalize()).len() < 1e-6);
+    }
+}
@@ -1,85 +1,637 @@
-use std::fs;
+use std::{collections::{HashMap, VecDeque}, path::Path, sync::Arc};
 
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{triangle::{Triangle}, point::Point, vector::Vector, matrix::Matrix};
+use crate::{triangle::Triangle, matrix::Matrix, material::Material, texture::Texture, point::Point, vector::Vector, color::Color, ray::Ray, intersection::Intersection, aabb::{Bounded, AABB}, obj::{self, ObjError}, stl::{self, StlError}, ply::{self, PlyError}, EPSILON};
 
+/// A triangle of a [`Mesh`]: the indices of its three vertices and its material.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Face {
+    pub vertices: [u32; 3],
+    pub material: usize
+}
+
+impl Face {
+    pub fn new(vertices: [u32; 3]) -> Face {
+        Face { vertices, material: 0 }
+    }
+
+    pub fn with_material(self, material: usize) -> Face {
+        Face { material, ..self }
+    }
+}
+
+/// How face normals are weighted when they are averaged into vertex normals.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalWeighting {
+    /// By face area, so large faces dominate.
+    Area,
+    /// By the angle of the face at the vertex, which does not depend on how
+    /// the surface around the vertex is tessellated.
+    Angle
+}
+
+/// Indexed triangle mesh. Vertices are stored once in shared buffers and
+/// faces only hold indices into them, so large models stay compact and
+/// transforms visit every vertex a single time.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Mesh {
-    pub triangles: Vec<Triangle>,
+    pub positions: Vec<Point>,
+    /// Unit vertex normals, either empty or one per position.
+    pub normals: Vec<Vector>,
+    /// Vertex texture coordinates, either empty or one per position.
+    pub uvs: Vec<(f32, f32)>,
+    /// Vertex colors multiplied into the albedo, either empty or one per position.
+    pub colors: Vec<Color>,
+    pub faces: Vec<Face>,
+    /// Materials loaded with the mesh. When empty, face materials are
+    /// scene material ids; otherwise they index into this list and
+    /// `Scene::add_mesh` registers them.
+    pub materials: Vec<Material>,
+    /// Textures referenced by `materials`.
+    pub textures: Vec<Arc<Texture>>
 }
 
 impl Mesh {
-    pub fn from_model<'a>(name: &'a str) -> Option<Self> {
-        let contents = fs::read_to_string(name);
-        if contents.is_err() {
-            return None;
+    pub fn new(positions: Vec<Point>, faces: Vec<Face>) -> Mesh {
+        Mesh { positions, normals: vec![], uvs: vec![], colors: vec![], faces, materials: vec![], textures: vec![] }
+    }
+
+    pub fn with_normals(self, normals: Vec<Vector>) -> Mesh {
+        assert_eq!(self.positions.len(), normals.len(), "one normal per vertex");
+        Mesh { normals: normals.into_iter().map(Vector::normalize).collect(), ..self }
+    }
+
+    pub fn with_uvs(self, uvs: Vec<(f32, f32)>) -> Mesh {
+        assert_eq!(self.positions.len(), uvs.len(), "one uv per vertex");
+        Mesh { uvs, ..self }
+    }
+
+    pub fn with_colors(self, colors: Vec<Color>) -> Mesh {
+        assert_eq!(self.positions.len(), colors.len(), "one color per vertex");
+        Mesh { colors, ..self }
+    }
+
+    /// Builds a mesh with three vertices per triangle. If only some of the
+    /// triangles carry an attribute, the others get their face normal, uv
+    /// `(0, 0)` or white in its place.
+    pub fn from_triangles(triangles: &[Triangle]) -> Mesh {
+        let has_normals = triangles.iter().any(|t| t.n1.is_some() && t.n2.is_some() && t.n3.is_some());
+        let has_uvs = triangles.iter().any(|t| t.uv1.is_some() && t.uv2.is_some() && t.uv3.is_some());
+        let has_colors = triangles.iter().any(|t| t.color1.is_some() && t.color2.is_some() && t.color3.is_some());
+
+        let mut mesh = Mesh::new(vec![], vec![]);
+        for triangle in triangles {
+            let first = mesh.positions.len() as u32;
+            mesh.positions.extend([triangle.v0, triangle.v1, triangle.v2]);
+            mesh.faces.push(Face::new([first, first + 1, first + 2]).with_material(triangle.material));
+            if has_normals {
+                let flat = (triangle.v1 - triangle.v0).cross(triangle.v2 - triangle.v0).normalize();
+                match (triangle.n1, triangle.n2, triangle.n3) {
+                    (Some(n1), Some(n2), Some(n3)) => mesh.normals.extend([n1, n2, n3].map(Vector::normalize)),
+                    _ => mesh.normals.extend([flat; 3])
+                }
+            }
+            if has_uvs {
+                match (triangle.uv1, triangle.uv2, triangle.uv3) {
+                    (Some(uv1), Some(uv2), Some(uv3)) => mesh.uvs.extend([uv1, uv2, uv3]),
+                    _ => mesh.uvs.extend([(0., 0.); 3])
+                }
+            }
+            if has_colors {
+                match (triangle.color1, triangle.color2, triangle.color3) {
+                    (Some(color1), Some(color2), Some(color3)) => mesh.colors.extend([color1, color2, color3]),
+                    _ => mesh.colors.extend([Color::white(); 3])
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Loads a Wavefront OBJ file, see [`crate::obj`] for the supported subset.
+    pub fn from_model<P: AsRef<Path>>(path: P) -> Result<Self, ObjError> {
+        obj::load_mesh(path)
+    }
+
+    /// Loads an ASCII or binary STL file, see [`crate::stl`].
+    pub fn from_stl<P: AsRef<Path>>(path: P) -> Result<Self, StlError> {
+        stl::load(path)
+    }
+
+    /// Loads an ASCII or binary PLY file, see [`crate::ply`].
+    pub fn from_ply<P: AsRef<Path>>(path: P) -> Result<Self, PlyError> {
+        ply::load(path)
+    }
+
+    /// Writes the mesh as Wavefront OBJ, see [`obj::write`].
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P) -> Result<(), ObjError> {
+        obj::save(self, path)
+    }
+
+    /// Writes the mesh as binary PLY, see [`ply::write`].
+    pub fn save_ply<P: AsRef<Path>>(&self, path: P) -> Result<(), PlyError> {
+        ply::save(self, path)
+    }
+
+    fn corners(&self, face: usize) -> [usize; 3] {
+        self.faces[face].vertices.map(|vertex| vertex as usize)
+    }
+
+    /// Face `face` as a standalone triangle with its vertex attributes.
+    pub fn triangle(&self, face: usize) -> Triangle {
+        let [a, b, c] = self.corners(face);
+        let mut triangle = Triangle::new(self.positions[a], self.positions[b], self.positions[c])
+            .with_material(self.faces[face].material);
+        if !self.normals.is_empty() {
+            triangle = Triangle { n1: Some(self.normals[a]), n2: Some(self.normals[b]), n3: Some(self.normals[c]), ..triangle };
+        }
+        if !self.uvs.is_empty() {
+            triangle = triangle.with_uvs(self.uvs[a], self.uvs[b], self.uvs[c]);
+        }
+        if !self.colors.is_empty() {
+            triangle = triangle.with_colors(self.colors[a], self.colors[b], self.colors[c]);
+        }
+        triangle
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        (0..self.faces.len()).map(|face| self.triangle(face))
+    }
+
+    pub fn face_aabb(&self, face: usize) -> AABB {
+        let [a, b, c] = self.corners(face);
+        Triangle::new(self.positions[a], self.positions[b], self.positions[c]).aabb()
+    }
+
+    /// Intersects `ray` with face `face`. Only a hit expands the face into a
+    /// full [`Triangle`] for shading.
+    pub fn intersect(&self, face: usize, ray: Ray) -> Option<Intersection> {
+        let [a, b, c] = self.corners(face);
+        let intersection = Triangle::new(self.positions[a], self.positions[b], self.positions[c]).intersect(ray)?;
+        Some(Intersection { object: self.triangle(face).into(), ..intersection })
+    }
+
+    /// Merges vertices closer than `tolerance` and gives every merged vertex
+    /// the area-weighted average normal of the faces around it, turning
+    /// faceted triangle soup such as STL into a smooth surface. Faces that
+    /// collapse in the process are dropped.
+    pub fn welded(self, tolerance: f32) -> Mesh {
+        let cell_size = tolerance.max(EPSILON);
+        let cell = |point: Point| ((point.x / cell_size).floor() as i64, (point.y / cell_size).floor() as i64, (point.z / cell_size).floor() as i64);
+        let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut positions: Vec<Point> = vec![];
+
+        let mut weld = |point: Point| -> usize {
+            let (x, y, z) = cell(point);
+            for neighbour in (x - 1..=x + 1).flat_map(|x| (y - 1..=y + 1).flat_map(move |y| (z - 1..=z + 1).map(move |z| (x, y, z)))) {
+                if let Some(candidates) = cells.get(&neighbour) {
+                    if let Some(&index) = candidates.iter().find(|&&index| (positions[index] - point).len() <= tolerance) {
+                        return index;
+                    }
+                }
+            }
+            positions.push(point);
+            cells.entry((x, y, z)).or_default().push(positions.len() - 1);
+            positions.len() - 1
+        };
+        let welded: Vec<usize> = self.positions.iter().map(|&point| weld(point)).collect();
+
+        let faces: Vec<Face> = self.faces.iter()
+            .filter(|face| {
+                let [a, b, c] = face.vertices.map(|vertex| welded[vertex as usize]);
+                a != b && b != c && a != c
+            })
+            .copied()
+            .collect();
+
+        let mut sums = vec![Vector::new(0., 0., 0.); positions.len()];
+        for face in &faces {
+            let [a, b, c] = face.vertices.map(|vertex| welded[vertex as usize]);
+            let face_normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+            for index in [a, b, c] {
+                sums[index] += face_normal;
+            }
+        }
+
+        // Vertices that only differ in position now coincide; keep one of
+        // each, but keep apart corners whose uvs or colors differ.
+        let mut mesh = Mesh::new(vec![], vec![]);
+        // Welded position and the bits of the uv and color.
+        type Key = (usize, Option<[u32; 2]>, Option<[u32; 3]>);
+        let mut vertices: HashMap<Key, u32> = HashMap::new();
+        let faces = faces.iter()
+            .map(|face| {
+                let [a, b, c] = face.vertices.map(|vertex| welded[vertex as usize]);
+                let flat = (positions[b] - positions[a]).cross(positions[c] - positions[a]).normalize();
+                let corners = face.vertices.map(|vertex| {
+                    let vertex = vertex as usize;
+                    let position = welded[vertex];
+                    let uv = self.uvs.get(vertex).map(|&(u, v)| [u.to_bits(), v.to_bits()]);
+                    let color = self.colors.get(vertex).map(|color| [color.r.to_bits(), color.g.to_bits(), color.b.to_bits()]);
+                    *vertices.entry((position, uv, color)).or_insert_with(|| {
+                        mesh.positions.push(positions[position]);
+                        // Opposite faces can cancel out; fall back to the face normal.
+                        let sum = sums[position];
+                        mesh.normals.push(if sum.len() > EPSILON { sum.normalize() } else { flat });
+                        mesh.uvs.extend(self.uvs.get(vertex));
+                        mesh.colors.extend(self.colors.get(vertex));
+                        mesh.positions.len() as u32 - 1
+                    })
+                });
+                Face { vertices: corners, ..*face }
+            })
+            .collect();
+
+        Mesh { faces, materials: self.materials, textures: self.textures, ..mesh }
+    }
+
+    /// Cross product of the edges of face `face`: its geometric normal with
+    /// twice its area as length.
+    fn face_normal(&self, face: usize) -> Vector {
+        let [a, b, c] = self.corners(face);
+        (self.positions[b] - self.positions[a]).cross(self.positions[c] - self.positions[a])
+    }
+
+    /// Id per vertex that is shared by all vertices at exactly the same
+    /// position, so seams in uvs or normals do not disconnect faces.
+    fn position_groups(&self) -> Vec<usize> {
+        let mut groups = HashMap::new();
+        self.positions.iter()
+            .map(|point| {
+                // Adding zero turns -0 into +0 so both land in the same group.
+                let key = [(point.x + 0.).to_bits(), (point.y + 0.).to_bits(), (point.z + 0.).to_bits()];
+                let next = groups.len();
+                *groups.entry(key).or_insert(next)
+            })
+            .collect()
+    }
+
+    /// Replaces the vertex normals with averages of the normals of the faces
+    /// around each position. Faces meeting at more than `crease_angle`
+    /// radians do not contribute to each other, so hard edges stay sharp;
+    /// vertices on such edges are split. Use `PI` to smooth everything.
+    pub fn with_smooth_normals(self, weighting: NormalWeighting, crease_angle: f32) -> Mesh {
+        let groups = self.position_groups();
+        let mut corners_around = vec![vec![]; groups.iter().max().map_or(0, |&group| group + 1)];
+        for (index, face) in self.faces.iter().enumerate() {
+            for (corner, &vertex) in face.vertices.iter().enumerate() {
+                corners_around[groups[vertex as usize]].push((index, corner));
+            }
+        }
+        let face_normals: Vec<Vector> = (0..self.faces.len()).map(|face| self.face_normal(face)).collect();
+        let unit_normals: Vec<Option<Vector>> = face_normals.iter()
+            .map(|normal| (normal.len() > 0.).then(|| normal.normalize()))
+            .collect();
+        let weight = |face: usize, corner: usize| match weighting {
+            NormalWeighting::Area => face_normals[face].len(),
+            NormalWeighting::Angle => {
+                let vertices = self.corners(face);
+                let origin = self.positions[vertices[corner]];
+                let e1 = (self.positions[vertices[(corner + 1) % 3]] - origin).normalize();
+                let e2 = (self.positions[vertices[(corner + 2) % 3]] - origin).normalize();
+                e1.dot(e2).clamp(-1., 1.).acos()
+            }
+        };
+        let min_cos = crease_angle.cos();
+
+        let mut mesh = Mesh::new(vec![], Vec::with_capacity(self.faces.len()));
+        let mut vertices: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+        for (index, face) in self.faces.iter().enumerate() {
+            let own = unit_normals[index];
+            let corners = face.vertices.map(|vertex| {
+                let mut sum = Vector::new(0., 0., 0.);
+                for &(other, corner) in &corners_around[groups[vertex as usize]] {
+                    let normal = match unit_normals[other] {
+                        Some(normal) => normal,
+                        None => continue
+                    };
+                    // Degenerate faces have no normal of their own and take every neighbour.
+                    let smooth = own.is_none_or(|own| other == index || own.dot(normal) >= min_cos);
+                    if smooth {
+                        sum += normal * weight(other, corner);
+                    }
+                }
+                let normal = if sum.len() > 0. { sum.normalize() } else { own.unwrap_or(sum) };
+                let key = (vertex, [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()]);
+                *vertices.entry(key).or_insert_with(|| {
+                    mesh.positions.push(self.positions[vertex as usize]);
+                    mesh.normals.push(normal);
+                    mesh.uvs.extend(self.uvs.get(vertex as usize));
+                    mesh.colors.extend(self.colors.get(vertex as usize));
+                    mesh.positions.len() as u32 - 1
+                })
+            });
+            mesh.faces.push(Face { vertices: corners, ..*face });
+        }
+
+        Mesh { materials: self.materials, textures: self.textures, ..mesh }
+    }
+
+    /// Directed edges of face `face` between position groups, skipping
+    /// edges of zero length.
+    fn edges(&self, groups: &[usize], face: usize) -> impl Iterator<Item = (usize, usize)> {
+        let [a, b, c] = self.corners(face).map(|vertex| groups[vertex]);
+        [(a, b), (b, c), (c, a)].into_iter().filter(|(from, to)| from != to)
+    }
+
+    /// Number of edges along which two faces have the same direction, i.e.
+    /// where the winding, and with it the facing, flips.
+    pub fn inconsistent_edges(&self) -> usize {
+        let groups = self.position_groups();
+        let mut directions: HashMap<(usize, usize), [usize; 2]> = HashMap::new();
+        for face in 0..self.faces.len() {
+            for (from, to) in self.edges(&groups, face) {
+                directions.entry((from.min(to), from.max(to))).or_default()[(from > to) as usize] += 1;
+            }
+        }
+        directions.values().filter(|[forward, backward]| *forward > 1 || *backward > 1).count()
+    }
+
+    /// Area-weighted sums of the face normals around each vertex.
+    fn face_normal_sums(&self) -> Vec<Vector> {
+        let mut sums = vec![Vector::new(0., 0., 0.); self.positions.len()];
+        for face in 0..self.faces.len() {
+            let normal = self.face_normal(face);
+            for vertex in self.corners(face) {
+                sums[vertex] += normal;
+            }
+        }
+        sums
+    }
+
+    /// Number of vertex normals pointing away from the faces they belong to.
+    pub fn flipped_normals(&self) -> usize {
+        self.normals.iter().zip(self.face_normal_sums())
+            .filter(|(normal, sum)| normal.dot(*sum) < 0.)
+            .count()
+    }
+
+    /// Flips faces so that neighbours agree on their winding, then turns
+    /// around vertex normals that point away from their faces. Each closed
+    /// connected part ends up facing outwards; open parts keep the facing
+    /// most of their faces already had.
+    pub fn with_consistent_winding(mut self) -> Mesh {
+        let groups = self.position_groups();
+        let mut faces_at_edge: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for face in 0..self.faces.len() {
+            for (from, to) in self.edges(&groups, face) {
+                faces_at_edge.entry((from.min(to), from.max(to))).or_default().push(face);
+            }
         }
-        let contents = contents.unwrap();
-        let mut points = vec![];
-        let mut normals = vec![];
-        let mut triangles = vec![];
-        for line in contents.lines() {
-            let parsed_line: Vec<&str> = line.split(" ").collect();
-            if parsed_line[0] == "v" {
-                let x = parsed_line[1].parse::<f32>().unwrap();
-                let y = parsed_line[2].parse::<f32>().unwrap();
-                let z = parsed_line[3].parse::<f32>().unwrap();
-                points.push(Point::new(x, y, z));
-            } else if parsed_line[0] == "vn" {
-                let x = parsed_line[1].parse::<f32>().unwrap();
-                let y = parsed_line[2].parse::<f32>().unwrap();
-                let z = parsed_line[3].parse::<f32>().unwrap();
-                normals.push(Vector::new(x, y, z));
-            } else if parsed_line[0] == "f" {
-                let mut triangle_data = Vec::with_capacity(3);
-                for i in 1..=3 {
-                    let parsed_indexes: Vec<&str> = parsed_line[i].split("/").collect();
-                    if parsed_indexes.len() == 1 {
-                        let point_index = parsed_indexes[0].parse::<usize>().unwrap();
-                        triangle_data.push((points[point_index-1], None));
-                    } else {
-                        let point_index = parsed_indexes[0].parse::<usize>().unwrap();
-                        let normal_index = parsed_indexes[2].parse::<usize>().unwrap();
-                        triangle_data.push((points[point_index-1], Some(normals[normal_index-1])));
+
+        let mut flipped: Vec<Option<bool>> = vec![None; self.faces.len()];
+        for seed in 0..self.faces.len() {
+            if flipped[seed].is_some() {
+                continue;
+            }
+            flipped[seed] = Some(false);
+            let mut part = vec![];
+            let mut closed = true;
+            let mut queue = VecDeque::from([seed]);
+            while let Some(face) = queue.pop_front() {
+                part.push(face);
+                let face_flipped = flipped[face] == Some(true);
+                for (from, to) in self.edges(&groups, face) {
+                    let (from, to) = if face_flipped { (to, from) } else { (from, to) };
+                    let neighbours = &faces_at_edge[&(from.min(to), from.max(to))];
+                    closed &= neighbours.len() == 2;
+                    for &neighbour in neighbours {
+                        if flipped[neighbour].is_none() {
+                            // A consistent neighbour runs along the shared edge the other way.
+                            let same_direction = self.edges(&groups, neighbour).any(|edge| edge == (from, to));
+                            flipped[neighbour] = Some(same_direction);
+                            queue.push_back(neighbour);
+                        }
                     }
                 }
-                if triangle_data[0].1.is_none() {
-                    let triangle = Triangle::new(triangle_data[0].0, triangle_data[1].0, triangle_data[2].0);
-                    triangles.push(triangle);
-                } else {
-                    let triangle = Triangle::with_normals(
-                        triangle_data[0].0,
-                        triangle_data[1].0,
-                        triangle_data[2].0,
-                        triangle_data[0].1.unwrap(),
-                        triangle_data[1].1.unwrap(),
-                        triangle_data[2].1.unwrap(),
-                    );
-                    triangles.push(triangle);
+            }
+
+            let flips = part.iter().filter(|&&face| flipped[face] == Some(true)).count();
+            let invert = if closed {
+                // Outward facing closed surfaces enclose a positive volume.
+                let origin = Point::new(0., 0., 0.);
+                let volume: f32 = part.iter()
+                    .map(|&face| {
+                        let [a, b, c] = self.corners(face).map(|vertex| self.positions[vertex] - origin);
+                        let volume = a.dot(b.cross(c));
+                        if flipped[face] == Some(true) { -volume } else { volume }
+                    })
+                    .sum();
+                volume < 0.
+            } else {
+                2 * flips > part.len()
+            };
+            if invert {
+                for &face in &part {
+                    flipped[face] = flipped[face].map(|flipped| !flipped);
                 }
             }
         }
-        println!("loaded");
-        Some(Mesh {
-            triangles
-        })
+
+        for (face, flipped) in self.faces.iter_mut().zip(flipped) {
+            if flipped == Some(true) {
+                face.vertices.swap(1, 2);
+            }
+        }
+        let sums = self.face_normal_sums();
+        for (normal, sum) in self.normals.iter_mut().zip(sums) {
+            if normal.dot(sum) < 0. {
+                *normal = -*normal;
+            }
+        }
+        self
+    }
+
+    /// Uses scene material `material` for every face, replacing any loaded materials.
+    pub fn with_material(self, material: usize) -> Mesh {
+        Mesh {
+            faces: self.faces.into_iter().map(|face| face.with_material(material)).collect(),
+            materials: vec![],
+            textures: vec![],
+            ..self
+        }
     }
 
-    pub fn apply_transform(&self, transform: &Matrix) -> Mesh {
-        let new_triangles = self.triangles.par_iter().map(|t| t.apply_transform(transform)).collect::<Vec<_>>();
+    pub fn apply_transform(self, transform: &Matrix) -> Mesh {
+        let normal_matrix = transform.normal_matrix();
         Mesh {
-            triangles: new_triangles
+            positions: self.positions.into_par_iter().map(|p| transform.transform_point(p)).collect(),
+            normals: self.normals.into_par_iter().map(|n| normal_matrix.transform_vector(n).normalize()).collect(),
+            ..self
         }
     }
 }
 
+impl Bounded for Mesh {
+    fn aabb(&self) -> AABB {
+        self.positions.iter().fold(AABB::empty(), |aabb, &point| aabb.merge(AABB::with_bounds(point, point)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::intersectable::Intersectable;
+
     use super::*;
 
+    fn quad() -> Mesh {
+        let positions = vec![Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(1., 1., 0.), Point::new(0., 1., 0.)];
+        Mesh::new(positions, vec![Face::new([0, 1, 2]), Face::new([0, 2, 3]).with_material(1)])
+            .with_uvs(vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)])
+    }
+
     #[test]
     fn test_from_model() {
-        Mesh::from_model("k.obj");
+        let mesh = Mesh::from_model("k.obj").unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(Mesh::from_model("missing.obj").is_err());
+    }
+
+    #[test]
+    fn test_triangle() {
+        let mesh = quad();
+        let triangle = mesh.triangle(1);
+        assert_eq!(Point::new(1., 1., 0.), triangle.v1);
+        assert_eq!(Some((0., 1.)), triangle.uv3);
+        assert_eq!(None, triangle.n1);
+        assert_eq!(1, triangle.material);
+        assert_eq!(AABB::with_bounds(Point::new(0., 0., 0.), Point::new(1., 1., 0.)), mesh.face_aabb(1));
+
+        let ray = Ray::new(Point::new(0.25, 0.75, 1.), Vector::new(0., 0., -1.));
+        assert_eq!(None, mesh.intersect(0, ray));
+        let intersection = mesh.intersect(1, ray).unwrap();
+        assert_eq!(1., intersection.t);
+        assert_eq!(Intersectable::from(triangle), intersection.object);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_from_triangles() {
+        let a = Triangle::with_normals(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.), Vector::new(0., 0., 2.), Vector::new(0., 0., 1.), Vector::new(0., 0., 1.));
+        let b = Triangle::new(Point::new(0., 0., 0.), Point::new(0., 1., 0.), Point::new(0., 0., 1.)).with_material(3);
+        let mesh = Mesh::from_triangles(&[a, b]);
+        assert_eq!(6, mesh.positions.len());
+        assert_eq!(a, mesh.triangle(0));
+        assert_eq!(Some(Vector::new(1., 0., 0.)), mesh.triangle(1).n2);
+        assert_eq!(3, mesh.triangle(1).material);
+        assert!(mesh.uvs.is_empty() && mesh.colors.is_empty());
+    }
+
+    #[test]
+    fn test_apply_transform() {
+        let mesh = quad().with_normals(vec![Vector::new(0., 0., 1.); 4]).apply_transform(&Matrix::translate(0., 0., 2.));
+        assert_eq!(4, mesh.positions.len());
+        assert_eq!(Point::new(1., 1., 2.), mesh.positions[2]);
+        assert_eq!(Vector::new(0., 0., 1.), mesh.normals[3]);
+        assert_eq!(AABB::with_bounds(Point::new(0., 0., 2.), Point::new(1., 1., 2.)), mesh.aabb());
+    }
+
+    #[test]
+    fn test_apply_transform_non_uniform() {
+        let normal = Vector::new(1., 0., 1.).normalize();
+        let mesh = quad().with_normals(vec![normal; 4]).apply_transform(&Matrix::scale(1., 1., 3.));
+        assert!((mesh.normals[0] - Vector::new(3., 0., 1.).normalize()).len() < 1e-6);
+    }
+
+    #[test]
+    fn test_welded() {
+        // Two facets of a folded square whose shared edge is slightly apart.
+        let a = Triangle::new(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.));
+        let b = Triangle::new(Point::new(1.0001, 0., 0.), Point::new(0., 0., -1.), Point::new(0., 1.0001, 0.)).with_material(2);
+        let sliver = Triangle::new(Point::new(0., 0., 0.), Point::new(0.0001, 0., 0.), Point::new(0., 1., 0.));
+        let mesh = Mesh::from_triangles(&[a, b, sliver]).welded(0.001);
+
+        assert_eq!(2, mesh.faces.len());
+        assert_eq!(4, mesh.positions.len());
+        assert_eq!(mesh.faces[0].vertices[1], mesh.faces[1].vertices[0]);
+        assert_eq!(mesh.faces[0].vertices[2], mesh.faces[1].vertices[2]);
+        assert_eq!(2, mesh.faces[1].material);
+        let shared = mesh.triangle(0).n2.unwrap();
+        assert!((shared - Vector::new(1., 1., 0.).normalize()).len() < 1e-3);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), mesh.triangle(0).n1);
+    }
+
+    #[test]
+    fn test_welded_keeps_uv_seams() {
+        let mesh = quad();
+        let mut seam = Mesh::from_triangles(&mesh.triangles().collect::<Vec<_>>());
+        seam.uvs[3] = (0.5, 0.);
+        let welded = seam.welded(0.001);
+        assert_eq!(5, welded.positions.len());
+        assert_eq!(Some((0., 0.)), welded.triangle(0).uv1);
+        assert_eq!(Some((0.5, 0.)), welded.triangle(1).uv1);
+    }
+
+    #[test]
+    fn test_smooth_normals_crease() {
+        // Two faces folded by 90 degrees along the edge from (0, 0, 0) to (0, 1, 0).
+        let positions = vec![Point::new(0., 0., 0.), Point::new(0., 1., 0.), Point::new(1., 0., 0.), Point::new(0., 0., 1.)];
+        let roof = Mesh::new(positions, vec![Face::new([0, 1, 2]), Face::new([0, 3, 1])]);
+
+        let creased = roof.clone().with_smooth_normals(NormalWeighting::Angle, 60f32.to_radians());
+        assert_eq!(6, creased.positions.len());
+        assert_eq!(Some(Vector::new(0., 0., -1.)), creased.triangle(0).n1);
+        assert_eq!(Some(Vector::new(-1., 0., 0.)), creased.triangle(1).n1);
+
+        let smooth = roof.with_smooth_normals(NormalWeighting::Angle, 100f32.to_radians());
+        assert_eq!(4, smooth.positions.len());
+        let shared = smooth.triangle(0).n2.unwrap();
+        assert!((shared - Vector::new(-1., 0., -1.).normalize()).len() < 1e-6);
+        assert_eq!(Some(Vector::new(0., 0., -1.)), smooth.triangle(0).n3);
+    }
+
+    #[test]
+    fn test_smooth_normals_weighting() {
+        // At the origin a large face with normal +z meets a small one with normal +y, both at right angles.
+        let positions = vec![Point::new(0., 0., 0.), Point::new(2., 0., 0.), Point::new(0., 2., 0.), Point::new(0., 0., 1.), Point::new(1., 0., 0.)];
+        let mesh = Mesh::new(positions, vec![Face::new([0, 1, 2]), Face::new([0, 3, 4])]);
+        let normal_at_origin = |weighting| mesh.clone().with_smooth_normals(weighting, std::f32::consts::PI).normals[0];
+        assert!((normal_at_origin(NormalWeighting::Area) - Vector::new(0., 1., 4.).normalize()).len() < 1e-6);
+        assert!((normal_at_origin(NormalWeighting::Angle) - Vector::new(0., 1., 1.).normalize()).len() < 1e-6);
+    }
+
+    fn tetrahedron(faces: [[u32; 3]; 4]) -> Mesh {
+        let positions = vec![Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.), Point::new(0., 0., 1.)];
+        Mesh::new(positions, faces.into_iter().map(Face::new).collect())
+    }
+
+    #[test]
+    fn test_consistent_winding_closed() {
+        let outward = tetrahedron([[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]]);
+        assert_eq!(0, outward.inconsistent_edges());
+
+        let mixed = tetrahedron([[0, 2, 1], [0, 3, 1], [0, 3, 2], [1, 3, 2]]);
+        assert_eq!(4, mixed.inconsistent_edges());
+        assert_eq!(outward, mixed.with_consistent_winding());
+
+        let inside_out = tetrahedron([[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]);
+        assert_eq!(0, inside_out.inconsistent_edges());
+        assert_eq!(outward, inside_out.with_consistent_winding());
+    }
+
+    #[test]
+    fn test_consistent_winding_open() {
+        // A strip of three faces facing +z, the middle one wound the other way.
+        let positions = vec![Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.), Point::new(1., 1., 0.), Point::new(2., 1., 0.)];
+        let strip = Mesh::new(positions, vec![Face::new([0, 1, 2]), Face::new([1, 2, 3]), Face::new([1, 4, 3])])
+            .with_normals(vec![Vector::new(0., 0., -1.); 5]);
+        assert_eq!(2, strip.inconsistent_edges());
+        // Vertices 2 and 3 sit between opposite faces and have no facing yet.
+        assert_eq!(3, strip.flipped_normals());
+
+        let repaired = strip.with_consistent_winding();
+        assert_eq!(0, repaired.inconsistent_edges());
+        assert_eq!(0, repaired.flipped_normals());
+        assert_eq!([1, 3, 2], repaired.faces[1].vertices);
+        assert_eq!(Vector::new(0., 0., 1.), repaired.normals[4]);
+    }
+
+    #[test]
+    fn test_from_stl() {
+        assert!(matches!(Mesh::from_stl("missing.stl"), Err(StlError::Io(..))));
+    }
+
+    #[test]
+    fn test_from_ply() {
+        assert!(matches!(Mesh::from_ply("missing.ply"), Err(PlyError::Io(..))));
+    }
+}
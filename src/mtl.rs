@@ -0,0 +1,258 @@
+//! Wavefront MTL material library parser.
+
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{color::Color, material::Material, obj::{ObjError, statements, floats}};
+
+/// Material as written in an MTL file, with texture paths resolved against
+/// the directory of the library.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub emission: Color,
+    pub shininess: f32,
+    pub ior: f32,
+    pub opacity: f32,
+    pub illum: u32,
+    pub diffuse_map: Option<PathBuf>,
+    pub bump_map: Option<PathBuf>,
+    pub bump_multiplier: f32
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> MtlMaterial {
+        MtlMaterial {
+            name: name.to_string(),
+            diffuse: Color::white(),
+            specular: Color::black(),
+            emission: Color::black(),
+            shininess: 0.,
+            ior: 1.,
+            opacity: 1.,
+            illum: 2,
+            diffuse_map: None,
+            bump_map: None,
+            bump_multiplier: 1.
+        }
+    }
+
+    /// Converts to a renderer material, using `illum` to pick mirrors and glass.
+    pub fn to_material(&self, albedo_texture: Option<usize>, bump_texture: Option<usize>) -> Material {
+        let highlights = self.illum >= 2;
+        let mirror = matches!(self.illum, 3 | 5 | 8);
+        let glass = matches!(self.illum, 4 | 6 | 7 | 9);
+
+        let mut material = Material::new(self.diffuse)
+            .with_specular(if highlights { self.specular.max_component() } else { 0. }, (2. / (self.shininess.max(0.) + 2.)).sqrt())
+            .with_emission(self.emission)
+            .with_opacity(if glass { 1. } else { self.opacity });
+        if mirror {
+            material = material.with_reflectivity(self.specular.max_component().min(1.));
+        }
+        if glass && self.ior > 1. {
+            material = material.with_ior(self.ior);
+        }
+        if let Some(texture) = albedo_texture {
+            material = material.with_albedo_texture(texture);
+        }
+        if let Some(texture) = bump_texture {
+            material = material.with_bump_texture(texture, self.bump_multiplier);
+        }
+        material
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<MtlMaterial>, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| ObjError::Io(path.to_path_buf(), error))?;
+    parse(&source, path)
+}
+
+/// Parses MTL source. Texture paths are taken relative to the directory of `file`.
+pub fn parse(source: &str, file: &Path) -> Result<Vec<MtlMaterial>, ObjError> {
+    let base_dir = file.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<MtlMaterial> = vec![];
+
+    for (line, statement) in statements(source) {
+        let error = |message: String| ObjError::Parse { file: file.to_path_buf(), line, message };
+        let mut tokens = statement.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if arguments.is_empty() {
+                return Err(error("newmtl needs a material name".to_string()));
+            }
+            materials.push(MtlMaterial::new(&arguments.join(" ")));
+            continue;
+        }
+
+        let known = matches!(keyword, "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" | "illum" | "map_Kd" | "map_Bump" | "map_bump" | "bump");
+        if !known {
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(error(format!("'{}' before any newmtl", keyword)))
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = color(&arguments).map_err(error)?,
+            "Ks" => material.specular = color(&arguments).map_err(error)?,
+            "Ke" => material.emission = color(&arguments).map_err(error)?,
+            "Ns" => material.shininess = floats::<1>(&arguments, 1, 1).map_err(error)?[0],
+            "Ni" => material.ior = floats::<1>(&arguments, 1, 1).map_err(error)?[0],
+            "d" => {
+                let arguments = arguments.strip_prefix(&["-halo"]).unwrap_or(&arguments);
+                material.opacity = floats::<1>(arguments, 1, 1).map_err(error)?[0];
+            },
+            "Tr" => material.opacity = 1. - floats::<1>(&arguments, 1, 1).map_err(error)?[0],
+            "illum" => {
+                material.illum = arguments.first()
+                    .filter(|_| arguments.len() == 1)
+                    .and_then(|illum| illum.parse().ok())
+                    .ok_or_else(|| error(format!("invalid illumination model '{}'", arguments.join(" "))))?;
+            },
+            "map_Kd" => material.diffuse_map = Some(base_dir.join(texture_map(&arguments).map_err(error)?.0)),
+            _ => {
+                let (path, multiplier) = texture_map(&arguments).map_err(error)?;
+                material.bump_map = Some(base_dir.join(path));
+                material.bump_multiplier = multiplier.unwrap_or(1.);
+            }
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Parses an `r g b` triple; a single value is used for all three channels.
+fn color(arguments: &[&str]) -> Result<Color, String> {
+    if matches!(arguments.first(), Some(&"spectral") | Some(&"xyz")) {
+        return Err(format!("'{}' colors are not supported", arguments[0]));
+    }
+    let [r, g, b] = floats::<3>(arguments, 1, 3)?;
+    Ok(if arguments.len() == 1 { Color::new(r, r, r) } else { Color::new(r, g, b) })
+}
+
+/// Splits a texture statement into the file name and the `-bm` multiplier.
+fn texture_map(arguments: &[&str]) -> Result<(String, Option<f32>), String> {
+    let mut multiplier = None;
+    let mut index = 0;
+    while index < arguments.len() && arguments[index].starts_with('-') {
+        let option = arguments[index];
+        let values = match option {
+            "-bm" | "-boost" | "-blendu" | "-blendv" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => 1,
+            "-mm" => 2,
+            // Offset, scale and turbulence may omit trailing components.
+            "-o" | "-s" | "-t" => arguments[index + 1..].iter()
+                .take(3)
+                .take_while(|value| value.parse::<f32>().is_ok())
+                .count(),
+            _ => return Err(format!("unknown texture option '{}'", option))
+        };
+        if values == 0 || index + values >= arguments.len() {
+            return Err(format!("option '{}' needs a value", option));
+        }
+        if option == "-bm" {
+            multiplier = Some(floats::<1>(&arguments[index + 1..index + 2], 1, 1)?[0]);
+        }
+        index += 1 + values;
+    }
+    if index >= arguments.len() {
+        return Err("missing texture file name".to_string());
+    }
+    Ok((arguments[index..].join(" "), multiplier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<Vec<MtlMaterial>, ObjError> {
+        parse(source, Path::new("assets/test.mtl"))
+    }
+
+    #[test]
+    fn test_parse() {
+        let source = "
+            # two materials
+            newmtl red paint
+            Ka 0 0 0
+            Kd 0.8 0.1 0.1
+            Ks 0.5
+            Ns 98
+            Ni 1.45
+            d 0.5
+            illum 2
+            map_Kd -s 2 2 textures/red.png
+            map_Bump -bm 0.25 -clamp on bump.png
+
+            newmtl glass
+            Tr 0.9
+            Ni 1.5
+            illum 4
+            Pr 0.3
+        ";
+        let materials = parse_str(source).unwrap();
+        assert_eq!(2, materials.len());
+
+        let red = &materials[0];
+        assert_eq!("red paint", red.name);
+        assert_eq!(Color::new(0.8, 0.1, 0.1), red.diffuse);
+        assert_eq!(Color::new(0.5, 0.5, 0.5), red.specular);
+        assert_eq!(98., red.shininess);
+        assert_eq!(0.5, red.opacity);
+        assert_eq!(Some(PathBuf::from("assets/textures/red.png")), red.diffuse_map);
+        assert_eq!(Some(PathBuf::from("assets/bump.png")), red.bump_map);
+        assert_eq!(0.25, red.bump_multiplier);
+
+        let glass = &materials[1];
+        assert!((glass.opacity - 0.1).abs() < 1e-6);
+        assert_eq!(4, glass.illum);
+    }
+
+    #[test]
+    fn test_to_material() {
+        let mut source = MtlMaterial::new("test");
+        source.diffuse = Color::new(0.5, 0.25, 0.);
+        source.specular = Color::new(0.2, 0.4, 0.1);
+        source.shininess = 6.;
+        source.ior = 1.45;
+        source.opacity = 0.5;
+
+        let material = source.to_material(Some(1), None);
+        assert_eq!(Color::new(0.5, 0.25, 0.), material.albedo);
+        assert_eq!(0.4, material.specular);
+        assert_eq!(6., material.shininess());
+        assert_eq!(0.5, material.opacity);
+        assert!(!material.is_dielectric());
+        assert_eq!(Some(1), material.albedo_texture);
+
+        source.illum = 7;
+        let glass = source.to_material(None, Some(2));
+        assert_eq!(1.45, glass.ior);
+        assert_eq!(1., glass.opacity);
+        assert_eq!(Some(2), glass.bump_texture);
+
+        source.illum = 3;
+        assert_eq!(0.4, source.to_material(None, None).reflectivity);
+        source.illum = 1;
+        assert_eq!(0., source.to_material(None, None).specular);
+    }
+
+    #[test]
+    fn test_errors() {
+        let message = |source: &str| parse_str(source).unwrap_err().to_string();
+        assert_eq!("assets/test.mtl:1: 'Kd' before any newmtl", message("Kd 1 1 1"));
+        assert_eq!("assets/test.mtl:2: invalid number 'red'", message("newmtl a\nKd red 0 0"));
+        assert_eq!("assets/test.mtl:2: missing texture file name", message("newmtl a\nmap_Kd -bm 2"));
+        assert_eq!("assets/test.mtl:2: unknown texture option '-foo'", message("newmtl a\nmap_Kd -foo a.png"));
+        assert_eq!("assets/test.mtl:2: invalid illumination model 'x'", message("newmtl a\nillum x"));
+    }
+}
@@ -0,0 +1,560 @@
+//! Wavefront OBJ parser and writer.
+
+use std::{collections::HashMap, fmt, fs, io::{self, BufWriter, Write}, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{point::Point, vector::Vector, triangle::Triangle, mesh::{Mesh, Face}, material::Material, mtl, texture::Texture, EPSILON};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse { file: PathBuf, line: usize, message: String },
+    Texture(PathBuf, image::ImageError)
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse { file, line, message } => write!(f, "{}:{}: {}", file.display(), line, message),
+            ObjError::Texture(path, error) => write!(f, "{}: {}", path.display(), error)
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObjVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObjFace {
+    pub vertices: [ObjVertex; 3],
+    /// Index into `Obj::groups` of the group the face was declared in.
+    pub group: usize,
+    /// Index into `Obj::material_names` of the active `usemtl`, if any.
+    pub material: Option<usize>
+}
+
+/// Contents of an OBJ file with all indices resolved to zero-based absolute ones.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Obj {
+    pub positions: Vec<Point>,
+    pub uvs: Vec<(f32, f32)>,
+    pub normals: Vec<Vector>,
+    pub faces: Vec<ObjFace>,
+    pub groups: Vec<String>,
+    pub material_names: Vec<String>,
+    pub material_libraries: Vec<String>
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Obj, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| ObjError::Io(path.to_path_buf(), error))?;
+    parse(&source, path)
+}
+
+/// Loads an OBJ file as a mesh together with the materials of its `mtllib` libraries.
+pub fn load_mesh<P: AsRef<Path>>(path: P) -> Result<Mesh, ObjError> {
+    let path = path.as_ref();
+    let obj = load(path)?;
+    let mut mesh = obj.to_mesh();
+    if obj.material_libraries.is_empty() {
+        return Ok(mesh);
+    }
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut library = HashMap::new();
+    for file in &obj.material_libraries {
+        for material in mtl::load(base_dir.join(file))? {
+            library.insert(material.name.clone(), material);
+        }
+    }
+
+    mesh.materials.push(Material::default());
+    let mut texture_ids = HashMap::new();
+    // Color maps are sRGB encoded while bump maps hold plain heights, so the
+    // same file loaded both ways gives two textures.
+    let mut load_texture = |mesh: &mut Mesh, path: &Option<PathBuf>, linear: bool| -> Result<Option<usize>, ObjError> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+        if let Some(&id) = texture_ids.get(&(path.clone(), linear)) {
+            return Ok(Some(id));
+        }
+        let texture = if linear { Texture::load_linear(path) } else { Texture::load(path) };
+        let texture = texture.map_err(|error| ObjError::Texture(path.clone(), error))?;
+        mesh.textures.push(Arc::new(texture));
+        texture_ids.insert((path.clone(), linear), mesh.textures.len() - 1);
+        Ok(Some(mesh.textures.len() - 1))
+    };
+
+    let mut material_ids = Vec::with_capacity(obj.material_names.len());
+    for name in &obj.material_names {
+        let id = match library.get(name) {
+            Some(source) => {
+                let albedo_texture = load_texture(&mut mesh, &source.diffuse_map, false)?;
+                let bump_texture = load_texture(&mut mesh, &source.bump_map, true)?;
+                mesh.materials.push(source.to_material(albedo_texture, bump_texture));
+                mesh.materials.len() - 1
+            },
+            None => 0
+        };
+        material_ids.push(id);
+    }
+
+    for (mesh_face, face) in mesh.faces.iter_mut().zip(&obj.faces) {
+        mesh_face.material = face.material.map_or(0, |material| material_ids[material]);
+    }
+
+    Ok(mesh)
+}
+
+/// Parses OBJ source; `file` is only used in error messages.
+pub fn parse(source: &str, file: &Path) -> Result<Obj, ObjError> {
+    let mut obj = Obj { groups: vec!["default".to_string()], ..Obj::default() };
+    let mut group = 0;
+    let mut material = None;
+
+    for (line, statement) in statements(source) {
+        let error = |message: String| ObjError::Parse { file: file.to_path_buf(), line, message };
+        let mut tokens = statement.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // A fourth number is a rational weight and three more after
+                // the position are a vertex color; neither is used.
+                if arguments.len() == 5 {
+                    return Err(error("expected 3, 4, 6 or 7 numbers, found 5".to_string()));
+                }
+                let [x, y, z, ..] = floats::<7>(&arguments, 3, 7).map_err(error)?;
+                obj.positions.push(Point::new(x, y, z));
+            },
+            "vn" => {
+                let [x, y, z] = floats::<3>(&arguments, 3, 3).map_err(error)?;
+                obj.normals.push(Vector::new(x, y, z));
+            },
+            "vt" => {
+                let [u, v] = floats::<2>(&arguments, 1, 3).map_err(error)?;
+                obj.uvs.push((u, v));
+            },
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(format!("face needs at least 3 vertices, found {}", arguments.len())));
+                }
+                let vertices = arguments.iter()
+                    .map(|vertex| parse_vertex(vertex, &obj))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                for i in 1..vertices.len() - 1 {
+                    obj.faces.push(ObjFace { vertices: [vertices[0], vertices[i], vertices[i + 1]], group, material });
+                }
+            },
+            "o" | "g" => {
+                let name = if arguments.is_empty() { "default".to_string() } else { arguments.join(" ") };
+                group = index_of(&mut obj.groups, name);
+            },
+            "usemtl" => {
+                if arguments.is_empty() {
+                    return Err(error("usemtl needs a material name".to_string()));
+                }
+                material = Some(index_of(&mut obj.material_names, arguments.join(" ")));
+            },
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(error("mtllib needs a file name".to_string()));
+                }
+                obj.material_libraries.extend(arguments.iter().map(|library| library.to_string()));
+            },
+            // Smoothing groups, free-form geometry, lines and render attributes
+            // such as `mg`, `lod` or `shadow_obj` have no effect on triangle meshes.
+            _ => {}
+        }
+    }
+
+    Ok(obj)
+}
+
+/// Splits source into statements paired with the line each one starts on.
+pub(crate) fn statements(source: &str) -> Vec<(usize, String)> {
+    let mut statements = vec![];
+    let mut pending = String::new();
+    let mut pending_line = 0;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        if pending.is_empty() {
+            pending_line = index + 1;
+        }
+
+        let line = raw_line.split('#').next().unwrap_or("");
+        if let Some(continued) = line.trim_end().strip_suffix('\\') {
+            pending.push_str(continued);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        statements.push((pending_line, std::mem::take(&mut pending)));
+    }
+    // A continuation on the last line has nothing left to join.
+    if !pending.is_empty() {
+        statements.push((pending_line, pending));
+    }
+
+    statements
+}
+
+fn index_of(names: &mut Vec<String>, name: String) -> usize {
+    match names.iter().position(|existing| *existing == name) {
+        Some(index) => index,
+        None => {
+            names.push(name);
+            names.len() - 1
+        }
+    }
+}
+
+/// Parses between `min` and `max` numbers, keeping the first `N` and
+/// defaulting missing ones to zero.
+pub(crate) fn floats<const N: usize>(arguments: &[&str], min: usize, max: usize) -> Result<[f32; N], String> {
+    if arguments.len() < min || arguments.len() > max {
+        return Err(format!("expected {} to {} numbers, found {}", min, max, arguments.len()));
+    }
+    let mut values = [0.; N];
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        *value = argument.parse().map_err(|_| format!("invalid number '{}'", argument))?;
+    }
+    Ok(values)
+}
+
+fn parse_vertex(token: &str, obj: &Obj) -> Result<ObjVertex, String> {
+    let mut parts = token.split('/');
+    let position = resolve_index(parts.next(), obj.positions.len(), "vertex", token)?
+        .ok_or_else(|| format!("missing vertex index in '{}'", token))?;
+    let uv = resolve_index(parts.next(), obj.uvs.len(), "texture coordinate", token)?;
+    let normal = resolve_index(parts.next(), obj.normals.len(), "normal", token)?;
+    if parts.next().is_some() {
+        return Err(format!("too many indices in '{}'", token));
+    }
+    Ok(ObjVertex { position, uv, normal })
+}
+
+/// Converts a one-based or negative (relative to the end) index into a
+/// zero-based one. Empty parts, as in `v//vn`, resolve to `None`.
+fn resolve_index(part: Option<&str>, count: usize, what: &str, token: &str) -> Result<Option<usize>, String> {
+    let part = match part {
+        Some(part) if !part.is_empty() => part,
+        _ => return Ok(None)
+    };
+    let index: i64 = part.parse().map_err(|_| format!("invalid {} index in '{}'", what, token))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        count as i64 + index
+    } else {
+        return Err(format!("{} index 0 in '{}'; OBJ indices start at 1", what, token));
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} in '{}' is out of range, {} defined so far", what, index, token, count));
+    }
+    Ok(Some(resolved as usize))
+}
+
+impl Obj {
+    pub fn triangle(&self, face: &ObjFace) -> Triangle {
+        let [a, b, c] = face.vertices;
+        let mut triangle = match (a.normal, b.normal, c.normal) {
+            (Some(n1), Some(n2), Some(n3)) => Triangle::with_normals(
+                self.positions[a.position],
+                self.positions[b.position],
+                self.positions[c.position],
+                self.normals[n1],
+                self.normals[n2],
+                self.normals[n3]
+            ),
+            _ => Triangle::new(self.positions[a.position], self.positions[b.position], self.positions[c.position])
+        };
+        if let (Some(uv1), Some(uv2), Some(uv3)) = (a.uv, b.uv, c.uv) {
+            triangle = triangle.with_uvs(self.uvs[uv1], self.uvs[uv2], self.uvs[uv3]);
+        }
+        triangle
+    }
+
+    /// Geometry of the file with every face using the default material.
+    pub fn to_mesh(&self) -> Mesh {
+        let corners = || self.faces.iter().flat_map(|face| face.vertices);
+        let has_normals = corners().any(|vertex| vertex.normal.is_some());
+        let has_uvs = corners().any(|vertex| vertex.uv.is_some());
+
+        let mut mesh = Mesh::new(vec![], Vec::with_capacity(self.faces.len()));
+        // Position, uv and normal indices, plus the face for unshared corners.
+        type Key = (usize, Option<usize>, Option<usize>, Option<usize>);
+        let mut vertices: HashMap<Key, u32> = HashMap::new();
+        for (index, face) in self.faces.iter().enumerate() {
+            let [a, b, c] = face.vertices.map(|vertex| self.positions[vertex.position]);
+            // Degenerate faces keep a zero normal; they are never hit.
+            let flat = (b - a).cross(c - a);
+            let flat = if flat.len() > EPSILON { flat.normalize() } else { flat };
+            let corners = face.vertices.map(|vertex| {
+                // Corners falling back to the face normal cannot be shared with other faces.
+                let unshared = (has_normals && vertex.normal.is_none()).then_some(index);
+                *vertices.entry((vertex.position, vertex.uv, vertex.normal, unshared)).or_insert_with(|| {
+                    mesh.positions.push(self.positions[vertex.position]);
+                    if has_normals {
+                        mesh.normals.push(vertex.normal.map_or(flat, |normal| self.normals[normal].normalize()));
+                    }
+                    if has_uvs {
+                        mesh.uvs.push(vertex.uv.map_or((0., 0.), |uv| self.uvs[uv]));
+                    }
+                    mesh.positions.len() as u32 - 1
+                })
+            });
+            mesh.faces.push(Face::new(corners));
+        }
+        mesh
+    }
+}
+
+/// Writes the geometry of `mesh` as OBJ. Materials are not written.
+pub fn write<W: Write>(mesh: &Mesh, writer: W) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    for position in &mesh.positions {
+        writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+    }
+    for (u, v) in &mesh.uvs {
+        writeln!(writer, "vt {} {}", u, v)?;
+    }
+    for normal in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+
+    // Attributes are stored per vertex, so all three indices of a corner match.
+    let corner = |index: u32| match (mesh.uvs.is_empty(), mesh.normals.is_empty()) {
+        (true, true) => format!("{}", index + 1),
+        (false, true) => format!("{0}/{0}", index + 1),
+        (true, false) => format!("{0}//{0}", index + 1),
+        (false, false) => format!("{0}/{0}/{0}", index + 1)
+    };
+    for face in &mesh.faces {
+        let [a, b, c] = face.vertices.map(corner);
+        writeln!(writer, "f {} {} {}", a, b, c)?;
+    }
+    writer.flush()
+}
+
+pub fn save<P: AsRef<Path>>(mesh: &Mesh, path: P) -> Result<(), ObjError> {
+    let path = path.as_ref();
+    let error = |error: io::Error| ObjError::Io(path.to_path_buf(), error);
+    write(mesh, fs::File::create(path).map_err(error)?).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<Obj, ObjError> {
+        parse(source, Path::new("test.obj"))
+    }
+
+    fn error_message(source: &str) -> String {
+        parse_str(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_index_forms() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 0 1
+            vn 0 0 1
+            f 1 2 3
+            f 1/1 2/2 3/3
+            f 1//1 2//1 3//1
+            f 1/1/1 2/2/1 3/3/1
+            f -3/-3/-1 -2/-2/-1 -1/-1/-1
+        ";
+        let obj = parse_str(source).unwrap();
+        assert_eq!(5, obj.faces.len());
+        let full = ObjVertex { position: 1, uv: Some(1), normal: Some(0) };
+        assert_eq!(ObjVertex { position: 1, uv: None, normal: None }, obj.faces[0].vertices[1]);
+        assert_eq!(ObjVertex { position: 1, uv: Some(1), normal: None }, obj.faces[1].vertices[1]);
+        assert_eq!(ObjVertex { position: 1, uv: None, normal: Some(0) }, obj.faces[2].vertices[1]);
+        assert_eq!(full, obj.faces[3].vertices[1]);
+        assert_eq!(full, obj.faces[4].vertices[1]);
+
+        let triangle = obj.triangle(&obj.faces[3]);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), triangle.n2);
+        assert_eq!(Some((1., 0.)), triangle.uv2);
+    }
+
+    #[test]
+    fn test_to_mesh_degenerate_face() {
+        let obj = parse_str("v 0 0 0\nv 1 0 0\nv 2 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 4//1\nf 1 2 3").unwrap();
+        let mesh = obj.to_mesh();
+        assert!(mesh.normals.iter().all(|normal| normal.x.is_finite() && normal.y.is_finite() && normal.z.is_finite()));
+        assert_eq!(Vector::new(0., 0., 0.), mesh.normals[mesh.faces[1].vertices[2] as usize]);
+    }
+
+    #[test]
+    fn test_to_mesh_shares_vertices() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 1
+            vn 0 0 2
+            f 1/1/1 2/1/1 3/1/1 4/1/1
+            f 1/2/1 3/1/1 4/1
+        ";
+        let mesh = parse_str(source).unwrap().to_mesh();
+        assert_eq!(3, mesh.faces.len());
+        // Four shared corners, one with a different uv and one without a normal.
+        assert_eq!(6, mesh.positions.len());
+        assert_eq!(mesh.faces[0].vertices[0], mesh.faces[1].vertices[0]);
+        assert_eq!(Vector::new(0., 0., 1.), mesh.normals[0]);
+        assert_eq!(Some((1., 1.)), mesh.triangle(2).uv1);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), mesh.triangle(2).n3);
+    }
+
+    #[test]
+    fn test_polygon_triangulation() {
+        let obj = parse_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 1 0\nf 1 2 3 4 5").unwrap();
+        let triangles: Vec<[usize; 3]> = obj.faces.iter()
+            .map(|face| face.vertices.map(|vertex| vertex.position))
+            .collect();
+        assert_eq!(vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]], triangles);
+    }
+
+    #[test]
+    fn test_whitespace_comments_and_continuations() {
+        let source = "# header\nv\t0  0 0 # origin\n  v 1 0 0\r\nv 0 1 0\nf 1 \\\n 2 \\\n 3\n\n";
+        let obj = parse_str(source).unwrap();
+        assert_eq!(3, obj.positions.len());
+        assert_eq!(1, obj.faces.len());
+
+        let obj = parse_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 \\\n 3 \\").unwrap();
+        assert_eq!(1, obj.faces.len());
+        let expected = vec![(1, "v 0 0 0".to_string()), (2, "f 1 2   3  ".to_string())];
+        assert_eq!(expected, statements("v 0 0 0\nf 1 2 \\\n 3 \\"));
+    }
+
+    #[test]
+    fn test_vertex_colors() {
+        let obj = parse_str("v 1 2 3 0.5 0.25 1\nv 4 5 6 1 0.5 0.25 1\nv 7 8 9 1").unwrap();
+        assert_eq!(vec![Point::new(1., 2., 3.), Point::new(4., 5., 6.), Point::new(7., 8., 9.)], obj.positions);
+        assert_eq!("test.obj:1: expected 3, 4, 6 or 7 numbers, found 5", error_message("v 1 2 3 4 5"));
+        assert_eq!("test.obj:1: invalid number 'red'", error_message("v 1 2 3 red 0 0"));
+    }
+
+    #[test]
+    fn test_unknown_statements() {
+        let source = "mg 1 0.5\nlod 2\nbevel on\nc_interp off\nshadow_obj shadow.obj\ntrace_obj trace.obj\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3";
+        let obj = parse_str(source).unwrap();
+        assert_eq!(3, obj.positions.len());
+        assert_eq!(1, obj.faces.len());
+    }
+
+    #[test]
+    fn test_groups_and_materials() {
+        let source = "mtllib a.mtl b.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\ng body\nusemtl skin\nf 1 2 3\no head\nusemtl eyes\nf 1 2 3\ng body\nusemtl skin\nf 1 2 3";
+        let obj = parse_str(source).unwrap();
+        assert_eq!(vec!["a.mtl", "b.mtl"], obj.material_libraries);
+        assert_eq!(vec!["default", "body", "head"], obj.groups);
+        assert_eq!(vec!["skin", "eyes"], obj.material_names);
+        let tags: Vec<(usize, Option<usize>)> = obj.faces.iter().map(|face| (face.group, face.material)).collect();
+        assert_eq!(vec![(0, None), (1, Some(0)), (2, Some(1)), (1, Some(0))], tags);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!("test.obj:2: invalid number 'x'", error_message("v 0 0 0\nv x 0 0"));
+        assert_eq!("test.obj:1: face needs at least 3 vertices, found 2", error_message("f 1 2"));
+        assert_eq!("test.obj:2: vertex index 2 in '2' is out of range, 1 defined so far", error_message("v 0 0 0\nf 1 2 1"));
+        assert_eq!("test.obj:2: vertex index 0 in '0'; OBJ indices start at 1", error_message("v 0 0 0\nf 0 1 1"));
+        assert_eq!("test.obj:2: normal index -1 in '1//-1' is out of range, 0 defined so far", error_message("v 0 0 0\nf 1//-1 1 1"));
+        assert_eq!("test.obj:2: invalid number 'y'", error_message("v 0 0 0\nv 1 \\\n y 0"));
+    }
+
+    #[test]
+    fn test_load_mesh_with_materials() {
+        let dir = std::env::temp_dir().join(format!("obj-materials-{}", std::process::id()));
+        fs::create_dir_all(dir.join("maps")).unwrap();
+        image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0])).save(dir.join("maps/red.png")).unwrap();
+        fs::write(dir.join("model.mtl"), "newmtl painted\nKd 1 1 1\nmap_Kd maps/red.png\nnewmtl also painted\nmap_Kd maps/red.png\nnewmtl shiny\nKs 0.5 0.5 0.5\nNs 6\n").unwrap();
+        fs::write(dir.join("model.obj"), "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nusemtl shiny\nf 1 2 3\nusemtl painted\nf 1 2 3\nusemtl also painted\nf 1 2 3\nusemtl unknown\nf 1 2 3\n").unwrap();
+
+        let mesh = load_mesh(dir.join("model.obj")).unwrap();
+        let materials: Vec<usize> = mesh.faces.iter().map(|face| face.material).collect();
+        assert_eq!(vec![0, 1, 2, 3, 0], materials);
+        assert_eq!(4, mesh.materials.len());
+        assert_eq!(0.5, mesh.materials[1].specular);
+        assert_eq!(Some(0), mesh.materials[2].albedo_texture);
+        assert_eq!(Some(0), mesh.materials[3].albedo_texture);
+        assert_eq!(1, mesh.textures.len());
+
+        fs::write(dir.join("broken.obj"), "mtllib missing.mtl\n").unwrap();
+        assert!(matches!(load_mesh(dir.join("broken.obj")), Err(ObjError::Io(path, _)) if path.ends_with("missing.mtl")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_bump_maps_are_linear() {
+        let dir = std::env::temp_dir().join(format!("obj-bump-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        image::RgbImage::from_pixel(1, 1, image::Rgb([128, 128, 128])).save(dir.join("gray.png")).unwrap();
+        fs::write(dir.join("model.mtl"), "newmtl bumpy\nmap_Kd gray.png\nbump gray.png\n").unwrap();
+        fs::write(dir.join("model.obj"), "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl bumpy\nf 1 2 3\n").unwrap();
+
+        let mesh = load_mesh(dir.join("model.obj")).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        let material = mesh.materials[1];
+        let color = mesh.textures[material.albedo_texture.unwrap()].sample(0.5, 0.5);
+        let height = mesh.textures[material.bump_texture.unwrap()].sample(0.5, 0.5);
+        assert_eq!(Color::from_srgb8(128, 128, 128), color);
+        assert_eq!(Color::new(128. / 255., 128. / 255., 128. / 255.), height);
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let positions = vec![Point::new(0., 0., 0.), Point::new(1.5, 0., 0.), Point::new(1.5, 1., -0.25), Point::new(0., 1e-3, 2.)];
+        let faces = vec![Face::new([0, 1, 2]), Face::new([0, 2, 3])];
+        let mesh = Mesh::new(positions, faces);
+        let with_normals = mesh.clone().with_normals(vec![Vector::new(0., 0., 1.), Vector::new(1., 0., 0.), Vector::new(0., 1., 0.), Vector::new(0., 0., -1.)]);
+        let with_uvs = with_normals.clone().with_uvs(vec![(0., 0.), (1., 0.), (1., 1.), (0.125, 1.)]);
+
+        for original in [mesh, with_normals, with_uvs] {
+            let mut bytes = vec![];
+            write(&original, &mut bytes).unwrap();
+            let loaded = parse(&String::from_utf8(bytes).unwrap(), Path::new("test.obj")).unwrap().to_mesh();
+            assert_eq!(original, loaded);
+        }
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("obj-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mesh = Mesh::new(vec![Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.)], vec![Face::new([0, 1, 2])]);
+        save(&mesh, dir.join("triangle.obj")).unwrap();
+        assert_eq!("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", fs::read_to_string(dir.join("triangle.obj")).unwrap());
+        assert_eq!(mesh, load_mesh(dir.join("triangle.obj")).unwrap());
+        assert!(matches!(save(&mesh, dir.join("missing/triangle.obj")), Err(ObjError::Io(..))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,14 +1,60 @@
 use crate::{point::Point, vector::Vector, ray::Ray, intersection::Intersection, matrix::Matrix, EPSILON, aabb::{Bounded, AABB}};
 
+/// How much of a plane is solid, measured from its point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extent {
+    Infinite,
+    /// Parallelogram spanning `point ± u ± v`.
+    Rectangle { u: Vector, v: Vector },
+    Disc { radius: f32 }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Plane {
     pub normal: Vector,
-    pub point: Point
+    pub point: Point,
+    pub extent: Extent,
+    pub material: usize
 }
 
 impl Plane {
     pub fn new(normal: Vector, point: Point) -> Plane {
-        Plane { normal, point }
+        Plane { normal, point, extent: Extent::Infinite, material: 0 }
+    }
+
+    pub fn with_material(self, material: usize) -> Plane {
+        Plane { material, ..self }
+    }
+
+    /// Limits the plane to a `width` by `height` rectangle centred on its
+    /// point, with edges along an arbitrary pair of directions in the plane.
+    /// Rotate it into place with [`Plane::apply_transform`].
+    pub fn with_rectangle(self, width: f32, height: f32) -> Plane {
+        let helper = if self.normal.x.abs() > 0.9 { Vector::new(0., 1., 0.) } else { Vector::new(1., 0., 0.) };
+        let u = helper.cross(self.normal).normalize();
+        let v = self.normal.cross(u).normalize();
+        Plane { extent: Extent::Rectangle { u: u * (width / 2.), v: v * (height / 2.) }, ..self }
+    }
+
+    /// Limits the plane to a disc of `radius` around its point.
+    pub fn with_disc(self, radius: f32) -> Plane {
+        Plane { extent: Extent::Disc { radius }, ..self }
+    }
+
+    fn contains(self, point: Point) -> bool {
+        let offset = point - self.point;
+        match self.extent {
+            Extent::Infinite => true,
+            Extent::Rectangle { u, v } => {
+                // Coordinates of the offset in the (u, v) basis.
+                let normal = u.cross(v);
+                let area = normal.len_sq();
+                let a = offset.cross(v).dot(normal) / area;
+                let b = u.cross(offset).dot(normal) / area;
+                a.abs() <= 1. && b.abs() <= 1.
+            },
+            Extent::Disc { radius } => offset.len_sq() <= radius * radius
+        }
     }
 
     pub fn intersect(self, ray: Ray) -> Option<Intersection> {
@@ -16,11 +62,13 @@ impl Plane {
         if denominator > EPSILON {
             let numerator = -self.normal.dot(self.point - ray.origin);
             let t = numerator / denominator;
-            if t >= 0.0 {
+            let point = ray.at(t);
+            if t >= 0.0 && self.contains(point) {
                 Some(Intersection {
                     t,
-                    point: ray.at(t),
-                    object: self.into()
+                    point,
+                    object: self.into(),
+                    barycentric: (0., 0.)
                 })
             } else {
                 None
@@ -34,20 +82,41 @@ impl Plane {
         self.normal
     }
 
+    /// Transforms the plane and its extent. Discs keep their area scaled by
+    /// the transform, which is exact unless it stretches the plane unevenly.
     pub fn apply_transform(self, transform: &Matrix) -> Plane {
+        let normal = transform.normal_matrix().transform_vector(self.normal);
+        let extent = match self.extent {
+            Extent::Infinite => Extent::Infinite,
+            Extent::Rectangle { u, v } => Extent::Rectangle { u: transform.transform_vector(u), v: transform.transform_vector(v) },
+            Extent::Disc { radius } => {
+                let area_scale = transform.determinant().abs() * normal.len() / self.normal.len();
+                Extent::Disc { radius: radius * area_scale.sqrt() }
+            }
+        };
         Plane {
-            normal: Vector::from(&transform.multiply(&self.normal.into())).normalize(),
-            point: (&transform.multiply(&self.point.into())).into()
+            normal: normal.normalize(),
+            point: transform.transform_point(self.point),
+            extent,
+            material: self.material
         }
     }
 }
 
 impl Bounded for Plane {
     fn aabb(&self) -> AABB {
-        AABB::with_bounds(
-            Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
-            Point::new(f32::INFINITY, f32::INFINITY, f32::INFINITY)
-        )
+        let reach = match self.extent {
+            Extent::Infinite => return AABB::full(),
+            Extent::Rectangle { u, v } => Vector::new(u.x.abs() + v.x.abs(), u.y.abs() + v.y.abs(), u.z.abs() + v.z.abs()),
+            Extent::Disc { radius } => {
+                // A disc reaches furthest along an axis where the normal is
+                // most perpendicular to it.
+                let normal = self.normal.normalize();
+                let reach = |n: f32| radius * (1. - n * n).max(0.).sqrt();
+                Vector::new(reach(normal.x), reach(normal.y), reach(normal.z))
+            }
+        };
+        AABB::with_bounds(self.point - reach, self.point + reach)
     }
 }
 
@@ -76,7 +145,7 @@ mod tests {
         let ray = Ray::new(origin, direction);
         let intersection = plane.intersect(ray);
         if let Some(intersection) = intersection {
-            assert_eq!(Point::new(0., 0., 0.), intersection.point);
+            assert!((Point::new(0., 0., 0.) - intersection.point).len() < EPSILON);
             assert_eq!((2f32).sqrt(), intersection.t);
             assert_eq!(Intersectable::from(plane), intersection.object);
         } else {
@@ -92,4 +161,63 @@ mod tests {
         let result  = plane.normal_at_point(point);
         assert_eq!(result, plane.normal);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_apply_transform() {
+        // A plane through (1, 0, 0) and (0, 1, 0), stretched along x.
+        let plane = Plane::new(Vector::new(1., 1., 0.).normalize(), Point::new(1., 0., 0.))
+            .apply_transform(&Matrix::scale(2., 1., 1.).multiply(&Matrix::translate(0., 0., 1.)));
+        assert_eq!(Point::new(2., 0., 1.), plane.point);
+        assert!((plane.normal - Vector::new(1., 2., 0.).normalize()).len() < 1e-6);
+        // Still perpendicular to the stretched surface.
+        assert!(plane.normal.dot(Point::new(0., 1., 1.) - plane.point).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rectangle() {
+        let plane = Plane::new(Vector::new(0., 1., 0.), Point::new(1., 0., 1.)).with_rectangle(2., 4.);
+        let down = |x, z| plane.intersect(Ray::new(Point::new(x, 1., z), Vector::new(0., -1., 0.)));
+        // The width runs along z and the height along x for this normal.
+        assert!(down(1., 1.).is_some());
+        assert!(down(2.9, 1.9).is_some());
+        assert!(down(-0.9, 0.1).is_some());
+        assert!(down(3.1, 1.).is_none());
+        assert!(down(1., 2.1).is_none());
+
+        let aabb = plane.aabb();
+        assert!(aabb.is_finite());
+        assert_eq!(0., aabb.size().y);
+        assert_eq!(8., aabb.size().x * aabb.size().z);
+    }
+
+    #[test]
+    fn test_disc() {
+        let plane = Plane::new(Vector::new(0., 0., 1.), Point::new(0., 0., 0.)).with_disc(1.);
+        let ray = |x, y| Ray::new(Point::new(x, y, 1.), Vector::new(0., 0., -1.));
+        assert!(plane.intersect(ray(0.7, 0.7)).is_some());
+        assert!(plane.intersect(ray(0.8, 0.8)).is_none());
+        assert_eq!(AABB::with_bounds(Point::new(-1., -1., 0.), Point::new(1., 1., 0.)), plane.aabb());
+
+        let tilted = Plane::new(Vector::new(1., 0., 1.).normalize(), Point::new(0., 0., 0.)).with_disc(2.);
+        let size = tilted.aabb().size();
+        assert!((size.x - 2f32.sqrt() * 2.).abs() < 1e-5);
+        assert!((size.y - 4.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_apply_transform_extent() {
+        let rectangle = Plane::new(Vector::new(0., 0., 1.), Point::new(0., 0., 0.))
+            .with_rectangle(2., 2.)
+            .apply_transform(&Matrix::rotate_x(std::f32::consts::FRAC_PI_2).multiply(&Matrix::scale(3., 3., 1.)));
+        assert!((rectangle.normal - Vector::new(0., -1., 0.)).len() < 1e-6);
+        let size = rectangle.aabb().size();
+        assert!((size.x - 6.).abs() < 1e-5 && (size.z - 6.).abs() < 1e-5 && size.y.abs() < 1e-5);
+
+        let disc = Plane::new(Vector::new(0., 0., 1.), Point::new(0., 0., 0.))
+            .with_disc(1.)
+            .apply_transform(&Matrix::translate(0., 0., 5.).multiply(&Matrix::scale(2., 2., 7.)));
+        assert_eq!(Extent::Disc { radius: 2. }, disc.extent);
+        assert_eq!(Point::new(0., 0., 5.), disc.point);
+        assert_eq!(Extent::Infinite, Plane::new(Vector::new(0., 1., 0.), Point::new(0., 0., 0.)).apply_transform(&Matrix::scale(2., 2., 2.)).extent);
+    }
+}
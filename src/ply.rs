@@ -0,0 +1,541 @@
+//! PLY parser and writer for the ASCII and binary formats.
+
+use std::{fmt, fs, io::{self, BufWriter, Write}, path::{Path, PathBuf}};
+
+use crate::{point::Point, vector::Vector, color::Color, mesh::{Mesh, Face}};
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(PathBuf, io::Error),
+    Header { file: PathBuf, line: usize, message: String },
+    /// Malformed or missing data for instance `index` of `element`.
+    Data { file: PathBuf, element: String, index: usize, message: String }
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlyError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            PlyError::Header { file, line, message } => write!(f, "{}:{}: {}", file.display(), line, message),
+            PlyError::Data { file, element, index, message } => write!(f, "{}: {} {}: {}", file.display(), element, index, message)
+        }
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::Int8,
+            "uchar" | "uint8" => Scalar::UInt8,
+            "short" | "int16" => Scalar::Int16,
+            "ushort" | "uint16" => Scalar::UInt16,
+            "int" | "int32" => Scalar::Int32,
+            "uint" | "uint32" => Scalar::UInt32,
+            "float" | "float32" => Scalar::Float32,
+            "double" | "float64" => Scalar::Float64,
+            _ => return None
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8
+        }
+    }
+
+    /// Largest value of integer types, used to normalize colors.
+    fn max_value(self) -> f64 {
+        match self {
+            Scalar::Int8 => i8::MAX as f64,
+            Scalar::UInt8 => u8::MAX as f64,
+            Scalar::Int16 => i16::MAX as f64,
+            Scalar::UInt16 => u16::MAX as f64,
+            Scalar::Int32 => i32::MAX as f64,
+            Scalar::UInt32 => u32::MAX as f64,
+            Scalar::Float32 | Scalar::Float64 => 1.
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Property {
+    name: String,
+    kind: PropertyKind
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|property| names.contains(&property.name.as_str()))
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, PlyError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|error| PlyError::Io(path.to_path_buf(), error))?;
+    parse(&bytes, path)
+}
+
+/// Parses PLY data; `file` is only used in error messages.
+pub fn parse(bytes: &[u8], file: &Path) -> Result<Mesh, PlyError> {
+    let (format, elements, body_start) = parse_header(bytes, file)?;
+    let mut body = Body { format, bytes: &bytes[body_start..], position: 0 };
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut colors = vec![];
+    let mut uvs = vec![];
+    let mut faces = vec![];
+
+    for element in &elements {
+        let error = |index: usize, message: String| PlyError::Data { file: file.to_path_buf(), element: element.name.clone(), index, message };
+
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [element.property(&["x"]), element.property(&["y"]), element.property(&["z"])];
+                let normal = [element.property(&["nx"]), element.property(&["ny"]), element.property(&["nz"])];
+                let color = [element.property(&["red", "diffuse_red"]), element.property(&["green", "diffuse_green"]), element.property(&["blue", "diffuse_blue"])];
+                let uv = [element.property(&["u", "s", "texture_u"]), element.property(&["v", "t", "texture_v"])];
+                let [Some(x), Some(y), Some(z)] = position else {
+                    return Err(error(0, "vertices need x, y and z properties".to_string()));
+                };
+                let used = position.iter().chain(&normal).chain(&color).chain(&uv).flatten();
+                for &property in used {
+                    if let PropertyKind::List { .. } = element.properties[property].kind {
+                        return Err(error(0, format!("property '{}' cannot be a list", element.properties[property].name)));
+                    }
+                }
+                let color_scale = match color[0].map(|red| element.properties[red].kind) {
+                    Some(PropertyKind::Scalar(scalar)) => scalar.max_value(),
+                    _ => 1.
+                };
+
+                for index in 0..element.count {
+                    let values = body.read_instance(element).map_err(|message| error(index, message))?;
+                    let value = |property: usize| values[property] as f32;
+                    positions.push(Point::new(value(x), value(y), value(z)));
+                    if let [Some(nx), Some(ny), Some(nz)] = normal {
+                        normals.push(Vector::new(value(nx), value(ny), value(nz)));
+                    }
+                    if let [Some(red), Some(green), Some(blue)] = color {
+                        let channel = |property: usize| (values[property] / color_scale) as f32;
+                        colors.push(Color::from_srgb(channel(red), channel(green), channel(blue)));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        uvs.push((value(u), value(v)));
+                    }
+                }
+            },
+            "face" => {
+                let indices = element.property(&["vertex_indices", "vertex_index"])
+                    .filter(|&property| matches!(element.properties[property].kind, PropertyKind::List { .. }))
+                    .ok_or_else(|| error(0, "faces need a vertex_indices list".to_string()))?;
+
+                for index in 0..element.count {
+                    let lists = body.read_lists(element, indices).map_err(|message| error(index, message))?;
+                    if lists.len() < 3 {
+                        return Err(error(index, format!("face needs at least 3 vertices, found {}", lists.len())));
+                    }
+                    let mut corners = Vec::with_capacity(lists.len());
+                    for &vertex in &lists {
+                        if vertex < 0. || vertex as usize >= positions.len() || vertex.fract() != 0. {
+                            return Err(error(index, format!("vertex index {} is out of range, {} vertices", vertex, positions.len())));
+                        }
+                        corners.push(vertex as u32);
+                    }
+                    for i in 1..corners.len() - 1 {
+                        faces.push(Face::new([corners[0], corners[i], corners[i + 1]]));
+                    }
+                }
+            },
+            _ => {
+                for index in 0..element.count {
+                    body.read_instance(element).map_err(|message| error(index, message))?;
+                }
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(positions, faces);
+    if !normals.is_empty() {
+        mesh = mesh.with_normals(normals);
+    }
+    if !colors.is_empty() {
+        mesh = mesh.with_colors(colors);
+    }
+    if !uvs.is_empty() {
+        mesh = mesh.with_uvs(uvs);
+    }
+    Ok(mesh)
+}
+
+fn parse_header(bytes: &[u8], file: &Path) -> Result<(Format, Vec<Element>, usize), PlyError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut position = 0;
+    let mut line_number = 0;
+
+    loop {
+        line_number += 1;
+        let error = |message: String| PlyError::Header { file: file.to_path_buf(), line: line_number, message };
+        let end = match bytes[position..].iter().position(|&byte| byte == b'\n') {
+            Some(end) => position + end,
+            None => return Err(error("missing end_header".to_string()))
+        };
+        let line = String::from_utf8_lossy(&bytes[position..end]);
+        position = end + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        if line_number == 1 {
+            if tokens != ["ply"] {
+                return Err(error("not a PLY file".to_string()));
+            }
+            continue;
+        }
+
+        match tokens.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {},
+            ["format", name, "1.0"] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(error(format!("unknown format '{}'", name)))
+                });
+            },
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| error(format!("invalid element count '{}'", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: vec![] });
+            },
+            ["property", "list", count, item, name] => {
+                let scalar = |name: &str| Scalar::parse(name).ok_or_else(|| error(format!("unknown property type '{}'", name)));
+                let kind = PropertyKind::List { count: scalar(count)?, item: scalar(item)? };
+                push_property(&mut elements, name, kind).map_err(error)?;
+            },
+            ["property", scalar, name] => {
+                let scalar = Scalar::parse(scalar).ok_or_else(|| error(format!("unknown property type '{}'", scalar)))?;
+                push_property(&mut elements, name, PropertyKind::Scalar(scalar)).map_err(error)?;
+            },
+            ["end_header"] => break,
+            _ => return Err(error(format!("unexpected '{}'", line.trim())))
+        }
+    }
+
+    match format {
+        Some(format) => Ok((format, elements, position)),
+        None => Err(PlyError::Header { file: file.to_path_buf(), line: line_number, message: "missing format".to_string() })
+    }
+}
+
+fn push_property(elements: &mut [Element], name: &str, kind: PropertyKind) -> Result<(), String> {
+    match elements.last_mut() {
+        Some(element) => {
+            element.properties.push(Property { name: name.to_string(), kind });
+            Ok(())
+        },
+        None => Err(format!("property '{}' before any element", name))
+    }
+}
+
+/// Cursor over the data section in any of the formats.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            return self.read_token();
+        }
+
+        let size = scalar.size();
+        let data = self.bytes.get(self.position..self.position + size).ok_or("unexpected end of data")?;
+        self.position += size;
+        let mut buffer = [0; 8];
+        buffer[..size].copy_from_slice(data);
+        if self.format == Format::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = buffer;
+        Ok(match scalar {
+            Scalar::Int8 => b0 as i8 as f64,
+            Scalar::UInt8 => b0 as f64,
+            Scalar::Int16 => i16::from_le_bytes([b0, b1]) as f64,
+            Scalar::UInt16 => u16::from_le_bytes([b0, b1]) as f64,
+            Scalar::Int32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::UInt32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Float32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Float64 => f64::from_le_bytes(buffer)
+        })
+    }
+
+    fn read_token(&mut self) -> Result<f64, String> {
+        let rest = &self.bytes[self.position..];
+        let start = rest.iter().position(|byte| !byte.is_ascii_whitespace()).ok_or("unexpected end of data")?;
+        let length = rest[start..].iter().position(|byte| byte.is_ascii_whitespace()).unwrap_or(rest.len() - start);
+        self.position += start + length;
+        let token = String::from_utf8_lossy(&rest[start..start + length]);
+        token.parse().map_err(|_| format!("invalid number '{}'", token))
+    }
+
+    fn read_list(&mut self, count: Scalar, item: Scalar) -> Result<Vec<f64>, String> {
+        let length = self.read(count)?;
+        if length < 0. {
+            return Err(format!("negative list length {}", length));
+        }
+        (0..length as usize).map(|_| self.read(item)).collect()
+    }
+
+    /// Reads every property of one instance, returning scalars by property
+    /// index; lists are skipped and left as zero.
+    fn read_instance(&mut self, element: &Element) -> Result<Vec<f64>, String> {
+        let mut values = vec![0.; element.properties.len()];
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            match property.kind {
+                PropertyKind::Scalar(scalar) => *value = self.read(scalar)?,
+                PropertyKind::List { count, item } => {
+                    self.read_list(count, item)?;
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Reads one instance and returns the list stored in property `wanted`.
+    fn read_lists(&mut self, element: &Element, wanted: usize) -> Result<Vec<f64>, String> {
+        let mut result = vec![];
+        for (index, property) in element.properties.iter().enumerate() {
+            match property.kind {
+                PropertyKind::Scalar(scalar) => {
+                    self.read(scalar)?;
+                },
+                PropertyKind::List { count, item } => {
+                    let list = self.read_list(count, item)?;
+                    if index == wanted {
+                        result = list;
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Writes `mesh` as binary little-endian PLY. Materials are not written.
+pub fn write<W: Write>(mesh: &Mesh, writer: W) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    writeln!(writer, "ply\nformat binary_little_endian 1.0")?;
+    writeln!(writer, "element vertex {}", mesh.positions.len())?;
+    writeln!(writer, "property float x\nproperty float y\nproperty float z")?;
+    if !mesh.normals.is_empty() {
+        writeln!(writer, "property float nx\nproperty float ny\nproperty float nz")?;
+    }
+    if !mesh.uvs.is_empty() {
+        writeln!(writer, "property float u\nproperty float v")?;
+    }
+    if !mesh.colors.is_empty() {
+        writeln!(writer, "property uchar red\nproperty uchar green\nproperty uchar blue")?;
+    }
+    writeln!(writer, "element face {}", mesh.faces.len())?;
+    writeln!(writer, "property list uchar uint vertex_indices\nend_header")?;
+
+    for (index, position) in mesh.positions.iter().enumerate() {
+        let mut floats = vec![position.x, position.y, position.z];
+        if let Some(normal) = mesh.normals.get(index) {
+            floats.extend([normal.x, normal.y, normal.z]);
+        }
+        if let Some(&(u, v)) = mesh.uvs.get(index) {
+            floats.extend([u, v]);
+        }
+        for value in floats {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        if let Some(color) = mesh.colors.get(index) {
+            writer.write_all(&color.to_srgb8())?;
+        }
+    }
+    for face in &mesh.faces {
+        writer.write_all(&[3])?;
+        for vertex in face.vertices {
+            writer.write_all(&vertex.to_le_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+pub fn save<P: AsRef<Path>>(mesh: &Mesh, path: P) -> Result<(), PlyError> {
+    let path = path.as_ref();
+    let error = |error: io::Error| PlyError::Io(path.to_path_buf(), error);
+    write(mesh, fs::File::create(path).map_err(error)?).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 1 0 255 0
+1 1 0 0 0 1 0 0 255
+0 1 0 0 0 1 255 255 255
+4 0 1 2 3
+";
+
+    fn parse_bytes(bytes: &[u8]) -> Result<Mesh, PlyError> {
+        parse(bytes, Path::new("test.ply"))
+    }
+
+    /// Binary version of a single triangle with an extra per-face property.
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut bytes = format!("ply\r\nformat {} 1.0\r\nelement vertex 3\r\nproperty double x\r\nproperty float y\r\nproperty float z\r\nproperty ushort red\r\nproperty ushort green\r\nproperty ushort blue\r\nelement face 1\r\nproperty uchar flags\r\nproperty list uchar uint vertex_indices\r\nend_header\r\n", format).into_bytes();
+        let mut push = |data: &[u8]| {
+            let mut data = data.to_vec();
+            if big_endian {
+                data.reverse();
+            }
+            bytes.extend(data);
+        };
+        for (x, y, red) in [(0., 0., 65535u16), (1., 0., 0), (0., 1., 0)] {
+            push(&f64::to_le_bytes(x));
+            push(&f32::to_le_bytes(y));
+            push(&f32::to_le_bytes(2.));
+            push(&red.to_le_bytes());
+            push(&0u16.to_le_bytes());
+            push(&0u16.to_le_bytes());
+        }
+        push(&[7]);
+        push(&[3]);
+        for index in [0u32, 1, 2] {
+            push(&index.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mesh = parse_bytes(ASCII.as_bytes()).unwrap();
+        assert_eq!(2, mesh.faces.len());
+        let triangle = mesh.triangle(1);
+        assert_eq!(Point::new(1., 1., 0.), triangle.v1);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), triangle.n3);
+        assert_eq!(Some(Color::new(0., 0., 1.)), triangle.color2);
+        assert_eq!(Some(Color::white()), triangle.color3);
+    }
+
+    #[test]
+    fn test_binary() {
+        for big_endian in [false, true] {
+            let mesh = parse_bytes(&binary(big_endian)).unwrap();
+            assert_eq!(1, mesh.faces.len());
+            let triangle = mesh.triangle(0);
+            assert_eq!(Point::new(1., 0., 2.), triangle.v1);
+            assert_eq!(None, triangle.n1);
+            assert_eq!(Some(Color::new(1., 0., 0.)), triangle.color1);
+            assert_eq!(Some(Color::black()), triangle.color2);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let message = |bytes: &[u8]| parse_bytes(bytes).unwrap_err().to_string();
+        assert_eq!("test.ply:1: not a PLY file", message(b"solid\n"));
+        assert_eq!("test.ply:2: unknown format 'binary_middle_endian'", message(b"ply\nformat binary_middle_endian 1.0\nend_header\n"));
+        assert_eq!("test.ply:3: unknown property type 'float128'", message(b"ply\nformat ascii 1.0\nproperty float128 x\n"));
+        assert_eq!("test.ply:3: missing end_header", message(b"ply\nformat ascii 1.0\n"));
+        let truncated = binary(false);
+        assert_eq!("test.ply: face 0: unexpected end of data", message(&truncated[..truncated.len() - 2]));
+        let out_of_range = ASCII.replace("4 0 1 2 3", "3 0 1 9");
+        assert_eq!("test.ply: face 0: vertex index 9 is out of range, 4 vertices", message(out_of_range.as_bytes()));
+        let listed = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty list uchar float x\nproperty float y\nproperty float z\nend_header\n1 5 0 0\n";
+        assert_eq!("test.ply: vertex 0: property 'x' cannot be a list", message(listed));
+        let listed = ASCII.replacen("property float nx", "property list uchar float nx", 1);
+        assert_eq!("test.ply: vertex 0: property 'nx' cannot be a list", message(listed.as_bytes()));
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let positions = vec![Point::new(0., 0., 0.), Point::new(1.5, 0., 0.), Point::new(1.5, 1., -0.25), Point::new(0., 1e-3, 2.)];
+        let mesh = Mesh::new(positions, vec![Face::new([0, 1, 2]), Face::new([3, 2, 0])]);
+        let mut bytes = vec![];
+        write(&mesh, &mut bytes).unwrap();
+        assert_eq!(mesh, parse_bytes(&bytes).unwrap());
+
+        let attributes = mesh
+            .with_normals(vec![Vector::new(0., 0., 1.), Vector::new(1., 0., 0.), Vector::new(0., 1., 0.), Vector::new(0., 0., -1.)])
+            .with_uvs(vec![(0., 0.), (1., 0.), (1., 1.), (0.125, 1.)])
+            .with_colors(vec![Color::white(), Color::black(), Color::new(1., 0., 0.), Color::new(0.5, 0.25, 0.)]);
+        let mut bytes = vec![];
+        write(&attributes, &mut bytes).unwrap();
+        let loaded = parse_bytes(&bytes).unwrap();
+        assert_eq!(Mesh { colors: loaded.colors.clone(), ..attributes.clone() }, loaded);
+        // Colors are quantized to 8-bit sRGB.
+        for (written, read) in attributes.colors.iter().zip(&loaded.colors) {
+            assert_eq!(written.to_srgb8(), read.to_srgb8());
+        }
+        assert_eq!(Color::new(1., 0., 0.), loaded.colors[2]);
+    }
+
+    #[test]
+    fn test_save() {
+        let path = std::env::temp_dir().join(format!("ply-save-{}.ply", std::process::id()));
+        let mesh = Mesh::new(vec![Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.)], vec![Face::new([0, 1, 2])]);
+        save(&mesh, &path).unwrap();
+        assert_eq!(mesh, load(&path).unwrap());
+        fs::remove_file(path).unwrap();
+        assert!(matches!(save(&mesh, "missing/dir/mesh.ply"), Err(PlyError::Io(..))));
+    }
+}
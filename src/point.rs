@@ -15,7 +15,7 @@ impl Point {
     }
 
     pub fn apply_transform(self, transform: Matrix) -> Point {
-        (&transform.multiply(&self.into())).into()
+        transform.transform_point(self)
     }
 }
 
@@ -58,12 +58,12 @@ impl From<&Matrix> for Point {
     }
 }
 
-impl Into<Matrix> for Point {
-    fn into(self) -> Matrix {
+impl From<Point> for Matrix {
+    fn from(v: Point) -> Matrix {
         m! [
-            self.x;
-            self.y;
-            self.z;
+            v.x;
+            v.y;
+            v.z;
             1.
         ]
     }
@@ -14,6 +14,11 @@ impl Ray {
     pub fn at(self, t: f32) -> Point {
         self.origin + self.direction * t
     }
+
+    /// Reciprocal of every direction component, infinite where it is zero.
+    pub fn inverse_direction(self) -> Vector {
+        Vector::new(1. / self.direction.x, 1. / self.direction.y, 1. / self.direction.z)
+    }
 }
 
 
@@ -39,4 +44,10 @@ mod tests {
         let result = ray.at(5.);
         assert_eq!(Point::new(8., 5., 8.), result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_inverse_direction() {
+        let ray = Ray { origin: Point::new(0., 0., 0.), direction: Vector::new(0.5, -0., 0.) };
+        assert_eq!(Vector::new(2., f32::NEG_INFINITY, f32::INFINITY), ray.inverse_direction());
+    }
+}
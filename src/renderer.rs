@@ -4,7 +4,11 @@ use image::{Rgb, RgbImage};
 use pbr::ProgressBar;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{scene::Scene, light::Light, color::Color, intersection::Intersection, ray::Ray, bvh::BVH, intersectable::Intersectable};
+use crate::{scene::Scene, color::{Color, ToneMapping}, bvh::BVH, integrator::Integrator, sampler::Sampler, filter::Filter};
+
+/// Filter weights that cancel out below this fraction of their absolute sum
+/// leave a normalized estimate dominated by noise.
+const MIN_WEIGHT_FRACTION: f32 = 0.5;
 
 pub enum Renderer<'a> {
     Console(Console<'a>),
@@ -27,7 +31,7 @@ pub struct Console<'a> {
 }
 
 impl<'a> Console<'a> {
-    pub fn new(scene: &'a Scene, width: u32, height: u32) -> Console {
+    pub fn new(scene: &'a Scene, width: u32, height: u32) -> Console<'a> {
         Console { scene, width, height }
     }
 
@@ -41,23 +45,19 @@ impl<'a> Console<'a> {
                 let mut symbol = ' ';
 
                 if let Some(intersection) = self.scene.closest_intersection(ray) {
-                    let Intersection { object, point, .. } = intersection;
-                    match self.scene.lights[0] {
-                        Light::Directional(light) => {
-                            let normal = object.normal_at_point(point);
-                            let product = -light.direction.dot(normal);
-                            if product < 0. {
-                                symbol = ' ';
-                            } else if product < 0.2 {
-                                symbol = '.';
-                            } else if product < 0.5 {
-                                symbol = '*';
-                            } else if product < 0.8 {
-                                symbol = 'O';
-                            } else {
-                                symbol = '#';
-                            }
-                        }
+                    let sample = self.scene.lights[0].sample(intersection.point);
+                    let normal = intersection.normal();
+                    let product = sample.direction.dot(normal);
+                    if product < 0. {
+                        symbol = ' ';
+                    } else if product < 0.2 {
+                        symbol = '.';
+                    } else if product < 0.5 {
+                        symbol = '*';
+                    } else if product < 0.8 {
+                        symbol = 'O';
+                    } else {
+                        symbol = '#';
                     }
                 }
 
@@ -74,25 +74,114 @@ impl<'a> Console<'a> {
     }
 }
 
+/// Everything about how an image is produced that is independent of what is in the scene.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub integrator: Integrator,
+    pub samples_per_pixel: u32,
+    pub sampler: Sampler,
+    pub filter: Filter,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f32
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            integrator: Integrator::default(),
+            samples_per_pixel: 1,
+            sampler: Sampler::default(),
+            filter: Filter::default(),
+            tone_mapping: ToneMapping::Aces,
+            exposure: 1.
+        }
+    }
+}
+
 pub struct Png<'a> {
     scene: &'a Scene,
     filename: String,
     width: u32,
-    height: u32
+    height: u32,
+    settings: RenderSettings
 }
 
 impl<'a> Png<'a> {
-    pub fn new(scene: &'a Scene, filename: String, width: u32, height: u32) -> Png {
-        Png { scene, filename, width, height }
+    pub fn new(scene: &'a Scene, filename: String, width: u32, height: u32) -> Png<'a> {
+        Png { scene, filename, width, height, settings: RenderSettings::default() }
     }
 
-    pub fn render(&self) {
-        let mut objects: Vec<Box<Intersectable>> = vec![];
-        for object in &self.scene.objects {
-            objects.push(Box::new(object.clone()));
+    pub fn with_settings(self, settings: RenderSettings) -> Png<'a> {
+        Png { settings, ..self }
+    }
+
+    pub fn with_integrator(self, integrator: Integrator) -> Png<'a> {
+        let settings = RenderSettings { integrator, ..self.settings };
+        self.with_settings(settings)
+    }
+
+    pub fn with_samples_per_pixel(self, samples_per_pixel: u32) -> Png<'a> {
+        let settings = RenderSettings { samples_per_pixel, ..self.settings };
+        self.with_settings(settings)
+    }
+
+    pub fn with_sampler(self, sampler: Sampler) -> Png<'a> {
+        let settings = RenderSettings { sampler, ..self.settings };
+        self.with_settings(settings)
+    }
+
+    pub fn with_filter(self, filter: Filter) -> Png<'a> {
+        let settings = RenderSettings { filter, ..self.settings };
+        self.with_settings(settings)
+    }
+
+    pub fn with_tone_mapping(self, tone_mapping: ToneMapping, exposure: f32) -> Png<'a> {
+        let settings = RenderSettings { tone_mapping, exposure, ..self.settings };
+        self.with_settings(settings)
+    }
+
+    /// Filtered estimate of the radiance reaching pixel `(x, y)`, counted from
+    /// the lower left corner. Samples are spread over the whole filter support,
+    /// so wide filters also draw from neighbouring pixels. When negative lobes
+    /// cancel most of the weight, as they can with few samples, the plain
+    /// mean of the samples is used instead.
+    fn pixel_radiance(&self, tree: &BVH, x: u32, y: u32) -> Color {
+        let RenderSettings { integrator, samples_per_pixel, sampler, filter, .. } = self.settings;
+        let radius = filter.radius();
+        let mut color = Color::black();
+        let mut total_weight = 0f32;
+        let mut absolute_weight = 0f32;
+        let mut unweighted = Color::black();
+        let mut count = 0;
+
+        for (u, v) in sampler.samples(samples_per_pixel.max(1), &mut rand::thread_rng()) {
+            let dx = (2. * u - 1.) * radius;
+            let dy = (2. * v - 1.) * radius;
+            let weight = filter.weight(dx, dy);
+            if weight == 0. {
+                continue;
+            }
+
+            let ray = self.scene.ray_for_sample(x as f32 + 0.5 + dx, y as f32 + 0.5 + dy);
+            let radiance = integrator.radiance(self.scene, tree, ray);
+            color += radiance * weight;
+            total_weight += weight;
+            absolute_weight += weight.abs();
+            unweighted += radiance;
+            count += 1;
         }
 
-        let tree = BVH::new(objects, 0, 2000);
+        if count == 0 {
+            Color::black()
+        } else if total_weight > MIN_WEIGHT_FRACTION * absolute_weight {
+            color / total_weight
+        } else {
+            unweighted / count as f32
+        }
+    }
+
+    pub fn render(&self) {
+        let tree = BVH::new(self.scene.objects.clone(), self.scene.meshes.clone(), self.scene.instances.clone());
         println!("Built");
 
         let thread_progress = Arc::new(AtomicU64::new(0));
@@ -118,40 +207,13 @@ impl<'a> Png<'a> {
             .into_par_iter()
             .map(|i| (i / self.height, i % self.height))
             .map(|(x, y)| {
-                let ray = self.scene.ray_for_pixel(x, self.height - y - 1);
-
-                let mut color = Color::new(0, 0, 0);
-
-                if let Some(intersection) = tree.intersect(ray) {
-                    let Intersection { object, point, .. } = intersection;
-                    for l in &self.scene.lights {
-                        match l {
-                            Light::Directional(light) => {
-                                let reverse_light_direction = -light.direction.normalize();
-                                let ray = Ray::new(point + reverse_light_direction * 0.00001, reverse_light_direction);
-
-                                if tree.intersect(ray) == None {
-                                    let normal = object.normal_at_point(point);
-
-                                    let product = reverse_light_direction.dot(normal);
-                                    if product < 0. {
-                                        continue;
-                                    }
-
-                                    color += Color::new(255, 255, 255) * product;
-                                } else {
-                                    // color += Color::new(20, 20, 20);
-                                }
-                            }
-                        }
-                    }
-                }
+                let color = self.pixel_radiance(&tree, x, self.height - y - 1);
 
                 if let Some(progress) = progress.as_ref() {
                     progress.fetch_add(1, Ordering::Relaxed);
                 }
 
-               color.into()
+                self.settings.tone_mapping.apply(color, self.settings.exposure).into()
             })
             .collect();
         
@@ -191,3 +253,37 @@ impl<'a> From<Png<'a>> for Renderer<'a> {
         Renderer::Png(png)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{camera::Perspective, point::Point, vector::Vector, material::Material, plane::Plane, integrator::PathTracer};
+
+    use super::*;
+
+    #[test]
+    fn test_mitchell_stays_bounded() {
+        // Stripes of radiance 0 and 1 one pixel wide put bright and dark
+        // samples under both lobes of the filter.
+        let mut scene = Scene::new(Perspective::new(Point::new(0., 0., 0.), 90., 1., 16).into(), vec![], vec![]);
+        let emitter = scene.add_material(Material::new(Color::black()).with_emission(Color::white()));
+        for i in 0..8 {
+            let x = -1. + i as f32 * 0.25;
+            let stripe = Plane::new(Vector::new(0., 0., 1.), Point::new(x + 0.0625, 0., -1.)).with_rectangle(4., 0.125);
+            scene.add_intersectable(stripe.with_material(emitter).into());
+        }
+        let tree = BVH::new(scene.objects.clone(), scene.meshes.clone(), scene.instances.clone());
+
+        for samples_per_pixel in 1..=4 {
+            let png = Png::new(&scene, String::new(), 16, 16)
+                .with_integrator(PathTracer::new(0).into())
+                .with_filter(Filter::Mitchell)
+                .with_samples_per_pixel(samples_per_pixel);
+            for _ in 0..50 {
+                for x in 0..16 {
+                    let color = png.pixel_radiance(&tree, x, 8);
+                    assert!(color.r.abs() <= 1. / MIN_WEIGHT_FRACTION + 1e-4, "{:?}", color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use rand::Rng;
+
+/// Strategy for placing the samples taken inside a square region of the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sampler {
+    /// Independent uniformly distributed samples.
+    Random,
+    /// One jittered sample per cell of the largest square grid that fits,
+    /// with any remaining samples placed at random.
+    #[default]
+    Stratified
+}
+
+impl Sampler {
+    /// Produces `count` sample positions in the unit square.
+    pub fn samples(self, count: u32, rng: &mut impl Rng) -> Vec<(f32, f32)> {
+        let mut samples = Vec::with_capacity(count as usize);
+
+        if self == Sampler::Stratified {
+            let cells = (count as f32).sqrt() as u32;
+            let cell_size = 1. / cells as f32;
+            for i in 0..cells {
+                for j in 0..cells {
+                    samples.push((
+                        (i as f32 + rng.gen::<f32>()) * cell_size,
+                        (j as f32 + rng.gen::<f32>()) * cell_size
+                    ));
+                }
+            }
+        }
+
+        while samples.len() < count as usize {
+            samples.push((rng.gen(), rng.gen()));
+        }
+
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let samples = Sampler::Random.samples(10, &mut rand::thread_rng());
+        assert_eq!(10, samples.len());
+        assert!(samples.iter().all(|&(x, y)| (0. ..1.).contains(&x) && (0. ..1.).contains(&y)));
+    }
+
+    #[test]
+    fn test_stratified() {
+        let samples = Sampler::Stratified.samples(4, &mut rand::thread_rng());
+        assert_eq!(4, samples.len());
+        let cells: Vec<(u32, u32)> = samples.iter().map(|&(x, y)| ((x * 2.) as u32, (y * 2.) as u32)).collect();
+        assert_eq!(vec![(0, 0), (0, 1), (1, 0), (1, 1)], cells);
+        assert_eq!(5, Sampler::Stratified.samples(5, &mut rand::thread_rng()).len());
+    }
+}
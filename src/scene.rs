@@ -1,9 +1,17 @@
-use crate::{intersectable::Intersectable, camera::Camera, ray::Ray, light::Light, intersection::Intersection, mesh::Mesh};
+use std::sync::Arc;
+
+use crate::{intersectable::Intersectable, camera::Camera, ray::Ray, light::Light, intersection::Intersection, mesh::Mesh, instance::Instance, material::Material, point::Point, texture::Texture, vector::Vector, EPSILON};
 
 pub struct Scene {
     pub camera: Camera,
     pub objects: Vec<Intersectable>,
+    /// Meshes stay indexed instead of being split into triangle objects.
+    pub meshes: Vec<Arc<Mesh>>,
+    /// Shared meshes placed by transforms, see [`Scene::add_instance`].
+    pub instances: Vec<Instance>,
     pub lights: Vec<Light>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Arc<Texture>>,
 }
 
 impl Scene {
@@ -11,7 +19,11 @@ impl Scene {
         Self {
             camera,
             objects,
-            lights
+            meshes: vec![],
+            instances: vec![],
+            lights,
+            materials: vec![Material::default()],
+            textures: vec![]
         }
     }
 
@@ -19,49 +31,155 @@ impl Scene {
         self.camera.ray_for_pixel(x, y)
     }
 
+    pub fn ray_for_sample(&self, x: f32, y: f32) -> Ray {
+        self.camera.ray_for_sample(x, y)
+    }
+
+    /// Focuses the camera on whatever is visible through pixel `(x, y)` and
+    /// returns the point it focused on, leaving the camera unchanged on a miss.
+    /// Only perspective cameras have a lens, so other projections return `None`.
+    pub fn focus_on_pixel(&mut self, x: u32, y: u32) -> Option<Point> {
+        let camera = match self.camera {
+            Camera::Perspective(camera) => camera,
+            _ => return None
+        };
+        let pinhole = camera.with_lens(0., camera.focus_distance);
+        let intersection = self.closest_intersection(pinhole.ray_for_pixel(x, y))?;
+        self.camera = camera.focus_on(intersection.point).into();
+        Some(intersection.point)
+    }
+
+    /// Every object, mesh face and instance face tested against `ray`, in scene order.
+    fn intersections(&self, ray: Ray) -> impl Iterator<Item = Intersection> + '_ {
+        let objects = self.objects.iter().filter_map(move |object| object.intersect(ray));
+        let faces = self.meshes.iter()
+            .flat_map(move |mesh| (0..mesh.faces.len()).filter_map(move |face| mesh.intersect(face, ray)));
+        let instances = self.instances.iter().flat_map(move |instance| {
+            let local = instance.object_ray(ray);
+            (0..instance.mesh.faces.len())
+                .filter_map(move |face| instance.mesh.intersect(face, local).map(|intersection| instance.world_intersection(intersection)))
+        });
+        objects.chain(faces).chain(instances)
+    }
+
     pub fn closest_intersection(&self, ray: Ray) -> Option<Intersection> {
         let mut closest_intersection: Option<Intersection> = None;
 
-        for object in &self.objects {
-            if let Some(intersection) = object.intersect(ray) {
-                if closest_intersection.is_none() || intersection.t < closest_intersection.unwrap().t {
-                    closest_intersection = Some(intersection);
-                }
+        for intersection in self.intersections(ray) {
+            if closest_intersection.is_none() || intersection.t < closest_intersection.unwrap().t {
+                closest_intersection = Some(intersection);
             }
         }
 
         closest_intersection
     }
 
-    pub fn intersection(&self, ray: Ray) -> Option<Intersection> {
-        for object in &self.objects {
-            if let Some(intersection) = object.intersect(ray) {
-                return Some(intersection)
-            }
-        }
+    /// Whether anything is hit closer than `t_max` along the ray.
+    pub fn occluded(&self, ray: Ray, t_max: f32) -> bool {
+        self.intersections(ray).any(|intersection| intersection.t < t_max)
+    }
 
-        None
+    pub fn intersection(&self, ray: Ray) -> Option<Intersection> {
+        self.intersections(ray).next()
     }
 
     pub fn add_intersectable(&mut self, intersectable: Intersectable) {
         self.objects.push(intersectable);
     }
 
+    /// Adds `mesh`, registering any materials and textures it was loaded
+    /// with and pointing its faces at the scene copies.
     pub fn add_mesh(&mut self, mesh: Mesh) {
-        for triangle in mesh.triangles {
-            self.objects.push(triangle.into());
+        let mesh = self.add_shared_mesh(mesh);
+        self.meshes.push(mesh);
+    }
+
+    /// Registers the materials and textures of `mesh` like [`Scene::add_mesh`]
+    /// without placing it, returning it ready to be instanced any number of
+    /// times.
+    pub fn add_shared_mesh(&mut self, mut mesh: Mesh) -> Arc<Mesh> {
+        if !mesh.materials.is_empty() {
+            let material_offset = self.materials.len();
+            let texture_offset = self.textures.len();
+            self.textures.append(&mut mesh.textures);
+            for material in mesh.materials.drain(..) {
+                self.materials.push(Material {
+                    albedo_texture: material.albedo_texture.map(|texture| texture + texture_offset),
+                    emission_texture: material.emission_texture.map(|texture| texture + texture_offset),
+                    bump_texture: material.bump_texture.map(|texture| texture + texture_offset),
+                    ..material
+                });
+            }
+            for face in &mut mesh.faces {
+                face.material += material_offset;
+            }
         }
+        Arc::new(mesh)
+    }
+
+    pub fn add_instance(&mut self, instance: Instance) {
+        self.instances.push(instance);
     }
 
     pub fn add_light(&mut self, light: Light) {
         self.lights.push(light);
     }
+
+    /// Registers a material and returns the index objects use to reference it.
+    /// Index 0 is always the default white diffuse material.
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    pub fn add_texture(&mut self, texture: Texture) -> usize {
+        self.textures.push(Arc::new(texture));
+        self.textures.len() - 1
+    }
+
+    pub fn material(&self, object: Intersectable) -> &Material {
+        &self.materials[object.material()]
+    }
+
+    /// Material and shading normal at `intersection` with vertex colors and
+    /// textures applied: both multiply the albedo, an emission map multiplies
+    /// the emission, and the bump map tilts the normal along the height
+    /// gradient.
+    pub fn surface(&self, intersection: &Intersection) -> (Material, Vector) {
+        let object = intersection.object;
+        let mut material = *self.material(object);
+        let mut normal = intersection.normal();
+        if let Some(color) = intersection.color() {
+            material.albedo = material.albedo * color;
+        }
+        let (u, v) = match intersection.uv() {
+            Some(uv) => uv,
+            None => return (material, normal)
+        };
+
+        if let Some(texture) = material.albedo_texture {
+            material.albedo = material.albedo * self.textures[texture].sample(u, v);
+        }
+        if let Some(texture) = material.emission_texture {
+            material.emission = material.emission * self.textures[texture].sample(u, v);
+        }
+        if let (Some(texture), Some((dp_du, dp_dv))) = (material.bump_texture, object.uv_tangents()) {
+            let (dh_du, dh_dv) = self.textures[texture].height_gradient(u, v);
+            let area = dp_du.cross(dp_dv).len();
+            if area > EPSILON {
+                let offset = normal.cross(dp_dv) * dh_du - normal.cross(dp_du) * dh_dv;
+                normal = (normal + offset * (material.bump_scale / area)).normalize();
+            }
+        }
+
+        (material, normal)
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::{intersectable::Intersectable, point::Point, sphere::Sphere, vector::Vector, plane::Plane};
+    use crate::{intersectable::Intersectable, camera::Perspective, sphere::Sphere, vector::Vector, plane::Plane, color::Color, triangle::Triangle, matrix::Matrix};
 
     use super::*;
 
@@ -77,7 +195,7 @@ mod tests {
         let normal = Vector::new(0., 1., 0.);
         let plane = Plane::new(normal, point);
         let objects: Vec<Intersectable> = vec![sphere1.into(), sphere2.into(), plane.into()];
-        let camera = Camera::new(Point::new(0., 0., 0.), 0., 0., 0);
+        let camera = Perspective::new(Point::new(0., 0., 0.), 0., 0., 0).into();
         let scene = Scene::new(camera, objects, vec![]);
         let origin = Point::new(0., 20., 0.);
         let direction = Vector::new(0., -1., 0.);
@@ -91,4 +209,92 @@ mod tests {
             panic!("No intersection");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_occluded() {
+        let camera = Perspective::new(Point::new(0., 0., 0.), 0., 0., 0).into();
+        let mut scene = Scene::new(camera, vec![Sphere::new(Point::new(0., 0., -5.), 1.).into()], vec![]);
+        scene.add_mesh(Mesh::from_triangles(&[Triangle::new(Point::new(-1., -1., 5.), Point::new(1., -1., 5.), Point::new(0., 1., 5.))]));
+        assert!(scene.occluded(Ray::new(Point::new(0., 0., 0.), Vector::new(0., 0., -1.)), 4.5));
+        assert!(!scene.occluded(Ray::new(Point::new(0., 0., 0.), Vector::new(0., 0., -1.)), 3.5));
+        assert!(scene.occluded(Ray::new(Point::new(0., 0., 0.), Vector::new(0., 0., 1.)), f32::INFINITY));
+        assert!(!scene.occluded(Ray::new(Point::new(0., 0., 0.), Vector::new(1., 0., 0.)), f32::INFINITY));
+    }
+
+    fn focus_distance(scene: &Scene) -> Option<f32> {
+        match scene.camera {
+            Camera::Perspective(camera) => Some(camera.focus_distance),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn test_focus_on_pixel() {
+        let camera = Perspective::new(Point::new(0., 0., 0.), 90., 1., 11).with_lens(0.1, 1.).into();
+        let mut scene = Scene::new(camera, vec![Sphere::new(Point::new(0., 0., -5.), 1.).into()], vec![]);
+        assert_eq!(Some(Point::new(0., 0., -4.)), scene.focus_on_pixel(5, 5));
+        assert_eq!(Some(4.), focus_distance(&scene));
+        assert_eq!(None, scene.focus_on_pixel(0, 0));
+        assert_eq!(Some(4.), focus_distance(&scene));
+    }
+
+    #[test]
+    fn test_add_material() {
+        let camera = Perspective::new(Point::new(0., 0., 0.), 0., 0., 0).into();
+        let mut scene = Scene::new(camera, vec![], vec![]);
+        let red = Material::new(Color::new(1., 0., 0.));
+        let id = scene.add_material(red);
+        assert_eq!(1, id);
+        let sphere: Intersectable = Sphere::new(Point::new(0., 0., 0.), 1.).with_material(id).into();
+        let plane: Intersectable = Plane::new(Vector::new(0., 1., 0.), Point::new(0., 0., 0.)).into();
+        assert_eq!(&red, scene.material(sphere));
+        assert_eq!(&Material::default(), scene.material(plane));
+    }
+
+    #[test]
+    fn test_add_mesh_with_materials() {
+        let camera = Perspective::new(Point::new(0., 0., 0.), 0., 0., 0).into();
+        let mut scene = Scene::new(camera, vec![], vec![]);
+        scene.add_material(Material::default());
+        scene.add_texture(Texture::new(1, 1, vec![Color::white()]));
+
+        let triangle = Triangle::new(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.))
+            .with_uvs((0., 0.), (1., 0.), (0., 1.));
+        let mut mesh = Mesh::from_triangles(&[triangle.with_material(1), triangle]);
+        mesh.materials = vec![Material::default(), Material::new(Color::new(1., 0.5, 0.)).with_albedo_texture(0)];
+        mesh.textures = vec![Arc::new(Texture::new(1, 1, vec![Color::new(0.5, 0.5, 0.5)]))];
+        scene.add_mesh(mesh);
+
+        assert!(scene.objects.is_empty());
+        assert_eq!(vec![3, 2], scene.meshes[0].faces.iter().map(|face| face.material).collect::<Vec<_>>());
+        assert!(scene.meshes[0].materials.is_empty());
+        assert_eq!(Some(1), scene.materials[3].albedo_texture);
+        let intersection = scene.closest_intersection(Ray::new(Point::new(0.25, 0.25, 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!(Intersectable::from(scene.meshes[0].triangle(0)), intersection.object);
+        let (material, normal) = scene.surface(&intersection);
+        assert_eq!(Color::new(0.5, 0.25, 0.), material.albedo);
+        assert_eq!(Vector::new(0., 0., 1.), normal);
+    }
+
+    #[test]
+    fn test_add_instance() {
+        let camera = Perspective::new(Point::new(0., 0., 0.), 0., 0., 0).into();
+        let mut scene = Scene::new(camera, vec![], vec![]);
+        let triangle = Triangle::new(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.));
+        let mut mesh = Mesh::from_triangles(&[triangle]);
+        mesh.materials = vec![Material::new(Color::new(1., 0., 0.))];
+        let mesh = scene.add_shared_mesh(mesh);
+        assert!(scene.meshes.is_empty());
+        assert_eq!(1, mesh.faces[0].material);
+
+        for x in 0..3 {
+            scene.add_instance(Instance::new(mesh.clone(), Matrix::translate(x as f32 * 2., 0., 0.)));
+        }
+        assert_eq!(4, Arc::strong_count(&mesh));
+
+        let intersection = scene.closest_intersection(Ray::new(Point::new(4.25, 0.25, 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!(Point::new(4.25, 0.25, 0.), intersection.point);
+        assert_eq!(Color::new(1., 0., 0.), scene.surface(&intersection).0.albedo);
+        assert!(scene.closest_intersection(Ray::new(Point::new(3.25, 0.25, 1.), Vector::new(0., 0., -1.))).is_none());
+    }
+}
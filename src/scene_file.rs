@@ -0,0 +1,748 @@
+//! Plain-text scene description; the format is described in `docs/scene-format.md`.
+
+use std::{collections::HashMap, fmt, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{
+    scene::Scene, camera::{Camera, Perspective, Orthographic, Fisheye, Equirectangular}, point::Point, vector::Vector,
+    color::{Color, ToneMapping}, material::Material, matrix::Matrix, mesh::{Mesh, NormalWeighting}, instance::Instance, sphere::Sphere, plane::Plane, triangle::Triangle,
+    light::{Light, Directional, PointLight, SpotLight, AreaLight}, renderer::RenderSettings, integrator::{Whitted, PathTracer},
+    sampler::Sampler, filter::Filter, EPSILON
+};
+
+/// A loaded scene together with the image it should be rendered to.
+pub struct SceneFile {
+    pub scene: Scene,
+    pub width: u32,
+    pub height: u32,
+    pub settings: RenderSettings
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(PathBuf, io::Error),
+    Parse { line: usize, message: String }
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            SceneFileError::Parse { line, message } => write!(f, "line {}: {}", line, message)
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+type Result<T> = std::result::Result<T, SceneFileError>;
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| SceneFileError::Io(path.to_path_buf(), error))?;
+    parse(&source, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// Parses a scene description, loading referenced meshes relative to `base_dir`.
+pub fn parse(source: &str, base_dir: &Path) -> Result<SceneFile> {
+    let mut parser = Parser {
+        base_dir,
+        width: 600,
+        height: 600,
+        settings: RenderSettings::default(),
+        max_depth: 5,
+        path_tracing: false,
+        camera: None,
+        scene: Scene::new(Perspective::new(Point::new(0., 0., 0.), 60., 1., 600).into(), vec![], vec![]),
+        materials: HashMap::new(),
+        shared_meshes: HashMap::new()
+    };
+
+    for (index, line) in source.lines().enumerate() {
+        let content = line.split('#').next().unwrap_or("");
+        let mut statement = Statement { line: index + 1, tokens: content.split_whitespace().collect(), position: 0 };
+        if statement.tokens.is_empty() {
+            continue;
+        }
+        parser.statement(&mut statement)?;
+    }
+
+    parser.finish()
+}
+
+struct Statement<'s> {
+    line: usize,
+    tokens: Vec<&'s str>,
+    position: usize
+}
+
+impl<'s> Statement<'s> {
+    fn error<T>(&self, message: String) -> Result<T> {
+        Err(SceneFileError::Parse { line: self.line, message })
+    }
+
+    fn next(&mut self) -> Option<&'s str> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn word(&mut self, what: &str) -> Result<&'s str> {
+        match self.next() {
+            Some(token) => Ok(token),
+            None => self.error(format!("expected {}", what))
+        }
+    }
+
+    fn float(&mut self, what: &str) -> Result<f32> {
+        let token = self.word(what)?;
+        match token.parse::<f32>() {
+            Ok(value) => Ok(value),
+            Err(_) => self.error(format!("expected a number for {}, found '{}'", what, token))
+        }
+    }
+
+    fn integer(&mut self, what: &str) -> Result<u32> {
+        let token = self.word(what)?;
+        match token.parse::<u32>() {
+            Ok(value) => Ok(value),
+            Err(_) => self.error(format!("expected a non-negative integer for {}, found '{}'", what, token))
+        }
+    }
+
+    fn triple(&mut self, what: &str) -> Result<(f32, f32, f32)> {
+        Ok((self.float(what)?, self.float(what)?, self.float(what)?))
+    }
+
+    fn vector(&mut self, what: &str) -> Result<Vector> {
+        self.triple(what).map(Vector::from)
+    }
+
+    fn point(&mut self, what: &str) -> Result<Point> {
+        self.triple(what).map(Point::from)
+    }
+
+    fn color(&mut self, what: &str) -> Result<Color> {
+        self.triple(what).map(|(r, g, b)| Color::new(r, g, b))
+    }
+
+    fn angle(&mut self, what: &str) -> Result<f32> {
+        self.float(what).map(f32::to_radians)
+    }
+
+    fn required<T>(&self, value: Option<T>, key: &str) -> Result<T> {
+        match value {
+            Some(value) => Ok(value),
+            None => self.error(format!("'{}' needs a '{}'", self.tokens[0], key))
+        }
+    }
+
+    fn unknown_key<T>(&self, key: &str) -> Result<T> {
+        self.error(format!("unknown key '{}' for '{}'", key, self.tokens[0]))
+    }
+}
+
+/// Camera statements are resolved once the whole file has been read so the
+/// image size may appear anywhere.
+struct CameraSpec {
+    projection: String,
+    eye: Point,
+    target: Point,
+    up: Vector,
+    roll: f32,
+    fov: Option<f32>,
+    view_height: f32,
+    aperture: f32,
+    focus: Option<f32>
+}
+
+struct Parser<'p> {
+    base_dir: &'p Path,
+    width: u32,
+    height: u32,
+    settings: RenderSettings,
+    max_depth: u32,
+    path_tracing: bool,
+    camera: Option<CameraSpec>,
+    scene: Scene,
+    materials: HashMap<String, usize>,
+    /// Meshes loaded for `instance` statements, so each file is read once.
+    shared_meshes: HashMap<MeshKey, Arc<Mesh>>
+}
+
+/// A mesh file with the weld tolerance, crease angle and material it was
+/// loaded with, floats compared by their bits.
+type MeshKey = (PathBuf, Option<u32>, Option<u32>, Option<usize>);
+
+impl<'p> Parser<'p> {
+    fn statement(&mut self, statement: &mut Statement) -> Result<()> {
+        match statement.word("a keyword")? {
+            "image" => {
+                self.width = statement.integer("image width")?;
+                self.height = statement.integer("image height")?;
+                if self.width == 0 || self.height == 0 {
+                    return statement.error("image size must be positive".to_string());
+                }
+            },
+            "render" => self.render(statement)?,
+            "camera" => self.camera(statement)?,
+            "material" => self.material(statement)?,
+            "light" => self.light(statement)?,
+            "sphere" => self.sphere(statement)?,
+            "plane" => self.plane(statement)?,
+            "triangle" => self.triangle(statement)?,
+            "mesh" => self.mesh(statement, false)?,
+            "instance" => self.mesh(statement, true)?,
+            keyword => return statement.error(format!("unknown statement '{}'", keyword))
+        }
+
+        if let Some(extra) = statement.next() {
+            return statement.error(format!("unexpected '{}'", extra));
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, statement: &mut Statement) -> Result<()> {
+        while let Some(key) = statement.next() {
+            match key {
+                "integrator" => {
+                    self.path_tracing = match statement.word("an integrator")? {
+                        "whitted" => false,
+                        "path" => true,
+                        other => return statement.error(format!("unknown integrator '{}'", other))
+                    }
+                },
+                "depth" => self.max_depth = statement.integer("depth")?,
+                "spp" => self.settings.samples_per_pixel = statement.integer("spp")?.max(1),
+                "sampler" => {
+                    self.settings.sampler = match statement.word("a sampler")? {
+                        "random" => Sampler::Random,
+                        "stratified" => Sampler::Stratified,
+                        other => return statement.error(format!("unknown sampler '{}'", other))
+                    }
+                },
+                "filter" => {
+                    self.settings.filter = match statement.word("a filter")? {
+                        "box" => Filter::Box,
+                        "tent" => Filter::Tent,
+                        "gaussian" => Filter::Gaussian,
+                        "mitchell" => Filter::Mitchell,
+                        other => return statement.error(format!("unknown filter '{}'", other))
+                    }
+                },
+                "tonemap" => {
+                    self.settings.tone_mapping = match statement.word("a tone mapping")? {
+                        "clamp" => ToneMapping::Clamp,
+                        "reinhard" => ToneMapping::Reinhard,
+                        "aces" => ToneMapping::Aces,
+                        other => return statement.error(format!("unknown tone mapping '{}'", other))
+                    }
+                },
+                "exposure" => self.settings.exposure = statement.float("exposure")?,
+                _ => return statement.unknown_key(key)
+            }
+        }
+        Ok(())
+    }
+
+    fn camera(&mut self, statement: &mut Statement) -> Result<()> {
+        let projection = statement.word("a projection")?;
+        if !["perspective", "orthographic", "fisheye", "equirectangular"].contains(&projection) {
+            return statement.error(format!("unknown projection '{}'", projection));
+        }
+        if self.camera.is_some() {
+            return statement.error("the scene already has a camera".to_string());
+        }
+
+        let mut spec = CameraSpec {
+            projection: projection.to_string(),
+            eye: Point::new(0., 0., 0.),
+            target: Point::new(0., 0., -1.),
+            up: Vector::new(0., 1., 0.),
+            roll: 0.,
+            fov: None,
+            view_height: 2.,
+            aperture: 0.,
+            focus: None
+        };
+
+        while let Some(key) = statement.next() {
+            match key {
+                "eye" => spec.eye = statement.point("eye")?,
+                "target" => spec.target = statement.point("target")?,
+                "up" => spec.up = statement.vector("up")?,
+                "roll" => spec.roll = statement.float("roll")?,
+                "fov" if projection == "perspective" || projection == "fisheye" => spec.fov = Some(statement.float("fov")?),
+                "height" if projection == "orthographic" => spec.view_height = statement.float("height")?,
+                "aperture" if projection == "perspective" => spec.aperture = statement.float("aperture")?,
+                "focus" if projection == "perspective" => spec.focus = Some(statement.float("focus")?),
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        if (spec.target - spec.eye).len() == 0. {
+            return statement.error("camera eye and target must differ".to_string());
+        }
+        if (spec.target - spec.eye).normalize().cross(spec.up).len() <= EPSILON * spec.up.len() {
+            return statement.error("camera up must not be parallel to the view direction".to_string());
+        }
+
+        self.camera = Some(spec);
+        Ok(())
+    }
+
+    fn material(&mut self, statement: &mut Statement) -> Result<()> {
+        let name = statement.word("a material name")?;
+        if self.materials.contains_key(name) {
+            return statement.error(format!("material '{}' is already defined", name));
+        }
+
+        let mut material = Material::default();
+        while let Some(key) = statement.next() {
+            match key {
+                "albedo" => material.albedo = statement.color("albedo")?,
+                "specular" => material.specular = statement.float("specular")?,
+                "roughness" => material.roughness = statement.float("roughness")?,
+                "emission" => material.emission = statement.color("emission")?,
+                "reflectivity" => material.reflectivity = statement.float("reflectivity")?,
+                "ior" => material.ior = statement.float("ior")?,
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        let id = self.scene.add_material(material);
+        self.materials.insert(name.to_string(), id);
+        Ok(())
+    }
+
+    fn light(&mut self, statement: &mut Statement) -> Result<()> {
+        let kind = statement.word("a light type")?;
+        let shape = if kind == "area" { Some(statement.word("an area light shape")?) } else { None };
+
+        let mut color = Color::white();
+        let mut intensity = None;
+        let mut direction = None;
+        let mut position = None;
+        let mut falloff = None;
+        let mut inner = None;
+        let mut outer = None;
+        let mut corner = None;
+        let mut edge1 = None;
+        let mut edge2 = None;
+        let mut radius = None;
+        let mut samples = None;
+
+        while let Some(key) = statement.next() {
+            match key {
+                "color" => color = statement.color("color")?,
+                "intensity" => intensity = Some(statement.float("intensity")?),
+                "direction" if kind == "directional" || kind == "spot" => direction = Some(statement.vector("direction")?),
+                "position" if kind == "point" || kind == "spot" => position = Some(statement.point("position")?),
+                "falloff" if kind == "point" || kind == "spot" => falloff = Some(statement.float("falloff")?),
+                "inner" if kind == "spot" => inner = Some(statement.angle("inner")?),
+                "outer" if kind == "spot" => outer = Some(statement.angle("outer")?),
+                "corner" if shape == Some("rectangle") => corner = Some(statement.point("corner")?),
+                "edge1" if shape == Some("rectangle") => edge1 = Some(statement.vector("edge1")?),
+                "edge2" if shape == Some("rectangle") => edge2 = Some(statement.vector("edge2")?),
+                "center" if shape == Some("sphere") => position = Some(statement.point("center")?),
+                "radius" if shape == Some("sphere") => radius = Some(statement.float("radius")?),
+                "samples" if kind == "area" => samples = Some(statement.integer("samples")?),
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        let intensity = intensity.unwrap_or(1.);
+        let light: Light = match (kind, shape) {
+            ("directional", _) => Directional::new(statement.required(direction, "direction")?, intensity).with_color(color).into(),
+            ("point", _) => {
+                let light = PointLight::new(statement.required(position, "position")?, intensity).with_color(color);
+                falloff.map_or(light, |falloff| light.with_falloff(falloff)).into()
+            },
+            ("spot", _) => {
+                let inner = statement.required(inner, "inner")?;
+                let light = SpotLight::new(
+                    statement.required(position, "position")?,
+                    statement.required(direction, "direction")?,
+                    intensity,
+                    inner,
+                    outer.unwrap_or(inner)
+                ).with_color(color);
+                falloff.map_or(light, |falloff| light.with_falloff(falloff)).into()
+            },
+            ("area", Some("rectangle")) => {
+                let light = AreaLight::rectangle(
+                    statement.required(corner, "corner")?,
+                    statement.required(edge1, "edge1")?,
+                    statement.required(edge2, "edge2")?,
+                    intensity
+                ).with_color(color);
+                samples.map_or(light, |samples| light.with_samples(samples)).into()
+            },
+            ("area", Some("sphere")) => {
+                let light = AreaLight::sphere(statement.required(position, "center")?, statement.required(radius, "radius")?, intensity).with_color(color);
+                samples.map_or(light, |samples| light.with_samples(samples)).into()
+            },
+            ("area", Some(other)) => return statement.error(format!("unknown area light shape '{}'", other)),
+            (other, _) => return statement.error(format!("unknown light type '{}'", other))
+        };
+
+        self.scene.add_light(light);
+        Ok(())
+    }
+
+    fn material_id(&self, statement: &mut Statement) -> Result<usize> {
+        let name = statement.word("a material name")?;
+        match self.materials.get(name) {
+            Some(&id) => Ok(id),
+            None => statement.error(format!("unknown material '{}'", name))
+        }
+    }
+
+    /// Parses a transform key if `key` is one, composing it after `transform`.
+    fn transform(statement: &mut Statement, key: &str, transform: &mut Option<Matrix>) -> Result<bool> {
+        let next = match key {
+            "scale" => {
+                let (x, y, z) = statement.triple("scale")?;
+                Matrix::scale(x, y, z)
+            },
+            "rotate_x" => Matrix::rotate_x(statement.angle("rotate_x")?),
+            "rotate_y" => Matrix::rotate_y(statement.angle("rotate_y")?),
+            "rotate_z" => Matrix::rotate_z(statement.angle("rotate_z")?),
+            "translate" => {
+                let (x, y, z) = statement.triple("translate")?;
+                Matrix::translate(x, y, z)
+            },
+            _ => return Ok(false)
+        };
+
+        *transform = Some(match transform.take() {
+            Some(previous) => next.multiply(&previous),
+            None => next
+        });
+        Ok(true)
+    }
+
+    fn sphere(&mut self, statement: &mut Statement) -> Result<()> {
+        let mut center = None;
+        let mut radius = None;
+        let mut material = 0;
+        let mut transform = None;
+
+        while let Some(key) = statement.next() {
+            match key {
+                "center" => center = Some(statement.point("center")?),
+                "radius" => radius = Some(statement.float("radius")?),
+                "material" => material = self.material_id(statement)?,
+                _ if Parser::transform(statement, key, &mut transform)? => {},
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        let mut sphere = Sphere::new(statement.required(center, "center")?, statement.required(radius, "radius")?).with_material(material);
+        if let Some(transform) = transform {
+            sphere = match sphere.apply_transform(&transform) {
+                Some(sphere) => sphere,
+                None => return statement.error("spheres can only be scaled uniformly".to_string())
+            };
+        }
+        self.scene.add_intersectable(sphere.into());
+        Ok(())
+    }
+
+    fn plane(&mut self, statement: &mut Statement) -> Result<()> {
+        let mut point = None;
+        let mut normal = None;
+        let mut rectangle = None;
+        let mut disc = None;
+        let mut material = 0;
+        let mut transform = None;
+
+        while let Some(key) = statement.next() {
+            match key {
+                "point" => point = Some(statement.point("point")?),
+                "normal" => normal = Some(statement.vector("normal")?.normalize()),
+                "rectangle" => rectangle = Some((statement.float("rectangle width")?, statement.float("rectangle height")?)),
+                "disc" => disc = Some(statement.float("disc")?),
+                "material" => material = self.material_id(statement)?,
+                _ if Parser::transform(statement, key, &mut transform)? => {},
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        if rectangle.is_some() && disc.is_some() {
+            return statement.error("plane takes either 'rectangle' or 'disc'".to_string());
+        }
+
+        let mut plane = Plane::new(statement.required(normal, "normal")?, statement.required(point, "point")?).with_material(material);
+        if let Some((width, height)) = rectangle {
+            plane = plane.with_rectangle(width, height);
+        }
+        if let Some(radius) = disc {
+            plane = plane.with_disc(radius);
+        }
+        if let Some(transform) = transform {
+            plane = plane.apply_transform(&transform);
+        }
+        self.scene.add_intersectable(plane.into());
+        Ok(())
+    }
+
+    fn triangle(&mut self, statement: &mut Statement) -> Result<()> {
+        let mut vertices = [None; 3];
+        let mut material = 0;
+        let mut transform = None;
+
+        while let Some(key) = statement.next() {
+            match key {
+                "v0" => vertices[0] = Some(statement.point("v0")?),
+                "v1" => vertices[1] = Some(statement.point("v1")?),
+                "v2" => vertices[2] = Some(statement.point("v2")?),
+                "material" => material = self.material_id(statement)?,
+                _ if Parser::transform(statement, key, &mut transform)? => {},
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        let mut triangle = Triangle::new(
+            statement.required(vertices[0], "v0")?,
+            statement.required(vertices[1], "v1")?,
+            statement.required(vertices[2], "v2")?
+        ).with_material(material);
+        if let Some(transform) = transform {
+            triangle = triangle.apply_transform(&transform);
+        }
+        self.scene.add_intersectable(triangle.into());
+        Ok(())
+    }
+
+    /// Reads a `mesh` statement, or an `instance` statement which shares the
+    /// mesh with every other instance of the same file and processing.
+    fn mesh(&mut self, statement: &mut Statement, instanced: bool) -> Result<()> {
+        let file = statement.word("a mesh file")?;
+        let mut material = None;
+        let mut transform = None;
+        let mut weld = None;
+        let mut crease_angle = None;
+
+        while let Some(key) = statement.next() {
+            match key {
+                "material" => material = Some(self.material_id(statement)?),
+                "weld" => weld = Some(statement.float("weld")?),
+                "smooth" => crease_angle = Some(statement.float("smooth")?.to_radians()),
+                _ if Parser::transform(statement, key, &mut transform)? => {},
+                _ => return statement.unknown_key(key)
+            }
+        }
+
+        let path = self.base_dir.join(file);
+        let key = (path.clone(), weld.map(f32::to_bits), crease_angle.map(f32::to_bits), material);
+        if instanced {
+            if transform.as_ref().is_some_and(|transform| transform.inverse().is_none()) {
+                return statement.error("instance transforms must be invertible".to_string());
+            }
+            if let Some(mesh) = self.shared_meshes.get(&key) {
+                let instance = Instance::new(mesh.clone(), transform.unwrap_or_else(Matrix::identity));
+                self.scene.add_instance(instance);
+                return Ok(());
+            }
+        }
+
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        let loaded = match extension.as_deref() {
+            Some("stl") => Mesh::from_stl(&path).map_err(|error| error.to_string()),
+            Some("ply") => Mesh::from_ply(&path).map_err(|error| error.to_string()),
+            _ => Mesh::from_model(&path).map_err(|error| error.to_string())
+        };
+        let mut mesh = match loaded {
+            Ok(mesh) => mesh,
+            Err(error) => return statement.error(format!("could not load mesh: {}", error))
+        };
+        if let Some(tolerance) = weld {
+            mesh = mesh.welded(tolerance);
+        }
+        if let Some(crease_angle) = crease_angle {
+            mesh = mesh.with_consistent_winding().with_smooth_normals(NormalWeighting::Angle, crease_angle);
+        }
+        if let Some(material) = material {
+            mesh = mesh.with_material(material);
+        }
+
+        if instanced {
+            let mesh = self.scene.add_shared_mesh(mesh);
+            self.shared_meshes.insert(key, mesh.clone());
+            self.scene.add_instance(Instance::new(mesh, transform.unwrap_or_else(Matrix::identity)));
+        } else {
+            if let Some(transform) = transform {
+                mesh = mesh.apply_transform(&transform);
+            }
+            self.scene.add_mesh(mesh);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<SceneFile> {
+        let aspect = self.width as f32 / self.height as f32;
+        let height = self.height;
+
+        if let Some(spec) = self.camera.take() {
+            let CameraSpec { eye, target, up, roll, .. } = spec;
+            self.scene.camera = match spec.projection.as_str() {
+                "orthographic" => Orthographic::look_at(eye, target, up, roll, spec.view_height, aspect, height).into(),
+                "fisheye" => Fisheye::look_at(eye, target, up, roll, spec.fov.unwrap_or(180.), aspect, height).into(),
+                "equirectangular" => Equirectangular::look_at(eye, target, up, roll, aspect, height).into(),
+                _ => {
+                    let camera = Perspective::look_at(eye, target, up, roll, spec.fov.unwrap_or(60.), aspect, height);
+                    let focus = spec.focus.unwrap_or_else(|| (target - eye).len());
+                    Camera::from(camera.with_lens(spec.aperture, focus))
+                }
+            };
+        } else {
+            self.scene.camera = Perspective::new(Point::new(0., 0., 0.), 60., aspect, height).into();
+        }
+
+        self.settings.integrator = if self.path_tracing {
+            PathTracer::new(self.max_depth).into()
+        } else {
+            Whitted::new(self.max_depth).into()
+        };
+
+        Ok(SceneFile {
+            scene: self.scene,
+            width: self.width,
+            height: self.height,
+            settings: self.settings
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{intersectable::Intersectable, integrator::Integrator, plane::Extent, aabb::Bounded, ray::Ray};
+
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<SceneFile> {
+        parse(source, Path::new("."))
+    }
+
+    fn error_line(source: &str) -> (usize, String) {
+        match parse_str(source) {
+            Err(SceneFileError::Parse { line, message }) => (line, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("expected an error")
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let source = "
+            # a small test scene
+            image 200 100
+            render integrator path spp 4 depth 3 filter tent
+            camera perspective eye 0 0 5 target 0 0 0 fov 40
+            material red albedo 1 0 0 specular 0.5   # trailing comment
+            light directional direction 0 -1 0 intensity 2
+            light point position 0 5 0 color 1 0.5 0.5
+            sphere center 0 0 0 radius 1 material red translate 1 0 0
+            plane point 0 -1 0 normal 0 1 0
+        ";
+        let file = parse_str(source).unwrap();
+        assert_eq!(200, file.width);
+        assert_eq!(100, file.height);
+        assert_eq!(4, file.settings.samples_per_pixel);
+        assert_eq!(Filter::Tent, file.settings.filter);
+        assert_eq!(Integrator::from(PathTracer::new(3)), file.settings.integrator);
+        assert_eq!(2, file.scene.lights.len());
+        assert_eq!(2, file.scene.objects.len());
+        assert_eq!(Color::new(1., 0., 0.), file.scene.materials[1].albedo);
+
+        match file.scene.objects[0] {
+            Intersectable::Sphere(sphere) => {
+                assert_eq!(Point::new(1., 0., 0.), sphere.center);
+                assert_eq!(1, sphere.material);
+            },
+            _ => panic!("expected a sphere")
+        }
+
+        let ray = file.scene.ray_for_sample(100., 50.);
+        assert_eq!(Point::new(0., 0., 5.), ray.origin);
+    }
+
+    #[test]
+    fn test_plane_extent() {
+        let file = parse_str("plane point 0 0 0 normal 0 1 0 rectangle 2 4\nplane point 0 0 0 normal 0 0 1 disc 3 translate 0 0 1").unwrap();
+        match (file.scene.objects[0], file.scene.objects[1]) {
+            (Intersectable::Plane(rectangle), Intersectable::Plane(disc)) => {
+                assert_eq!(8., rectangle.aabb().size().x * rectangle.aabb().size().z);
+                assert_eq!(Extent::Disc { radius: 3. }, disc.extent);
+                assert_eq!(Point::new(0., 0., 1.), disc.point);
+            },
+            _ => panic!("expected planes")
+        }
+    }
+
+    #[test]
+    fn test_instances() {
+        let dir = std::env::temp_dir().join(format!("scene-instances-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("triangle.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let source = "
+            material red albedo 1 0 0
+            instance triangle.obj translate 0 0 -1
+            instance triangle.obj translate 2 0 -1
+            instance triangle.obj scale 2 2 2 material red
+            mesh triangle.obj
+        ";
+        let file = parse(source, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let instances = &file.scene.instances;
+        assert_eq!(3, instances.len());
+        assert!(Arc::ptr_eq(&instances[0].mesh, &instances[1].mesh));
+        assert!(!Arc::ptr_eq(&instances[0].mesh, &instances[2].mesh));
+        assert_eq!(1, instances[2].mesh.faces[0].material);
+        assert_eq!(1, file.scene.meshes.len());
+
+        let hit = file.scene.closest_intersection(Ray::new(Point::new(2.25, 0.25, 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!(Point::new(2.25, 0.25, -1.), hit.point);
+
+        assert_eq!((1, "instance transforms must be invertible".to_string()), error_line("instance triangle.obj scale 0 1 1"));
+    }
+
+    #[test]
+    fn test_transform_order() {
+        let file = parse_str("sphere center 1 0 0 radius 1 rotate_z 90 translate 0 0 2").unwrap();
+        match file.scene.objects[0] {
+            Intersectable::Sphere(sphere) => assert!((sphere.center - Point::new(0., 1., 2.)).len() < 1e-6),
+            _ => panic!("expected a sphere")
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!((2, "unknown statement 'cube'".to_string()), error_line("image 10 10\ncube size 1"));
+        assert_eq!((1, "unknown material 'gold'".to_string()), error_line("sphere center 0 0 0 radius 1 material gold"));
+        assert_eq!((3, "expected a number for radius, found 'big'".to_string()), error_line("\n\nsphere center 0 0 0 radius big"));
+        assert_eq!((1, "'sphere' needs a 'radius'".to_string()), error_line("sphere center 0 0 0"));
+        assert_eq!((1, "unknown key 'radius' for 'plane'".to_string()), error_line("plane point 0 0 0 normal 0 1 0 radius 2"));
+        assert_eq!((1, "plane takes either 'rectangle' or 'disc'".to_string()), error_line("plane point 0 0 0 normal 0 1 0 rectangle 2 4 disc 3"));
+        assert_eq!((1, "expected center".to_string()), error_line("sphere radius 1 center 0 0"));
+        assert_eq!((1, "unexpected 'extra'".to_string()), error_line("image 10 10 extra"));
+        assert_eq!((2, "material 'a' is already defined".to_string()), error_line("material a\nmaterial a"));
+        assert_eq!((1, "unknown light type 'laser'".to_string()), error_line("light laser"));
+        assert_eq!((2, "camera up must not be parallel to the view direction".to_string()), error_line("\ncamera perspective eye 0 5 0 target 0 0 0"));
+        assert_eq!((1, "camera up must not be parallel to the view direction".to_string()), error_line("camera perspective up 0 0 0"));
+        assert_eq!((1, "unknown key 'fov' for 'camera'".to_string()), error_line("camera orthographic fov 40"));
+        assert_eq!((1, "spheres can only be scaled uniformly".to_string()), error_line("sphere center 0 0 0 radius 1 scale 1 2 1"));
+        assert_eq!((1, "unknown key 'fov' for 'camera'".to_string()), error_line("camera equirectangular fov 40"));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(matches!(load("does/not/exist.scene"), Err(SceneFileError::Io(_, _))));
+        let (line, message) = error_line("\nmesh missing.obj");
+        assert_eq!(2, line);
+        assert!(message.contains("missing.obj"));
+        let (_, message) = error_line("mesh missing.STL weld 0.01 smooth 30");
+        assert!(message.contains("missing.STL"));
+    }
+}
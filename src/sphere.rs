@@ -3,12 +3,17 @@ use crate::{point::Point, ray::Ray, intersection::Intersection, vector::Vector,
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Sphere {
     pub center: Point,
-    pub radius: f32
+    pub radius: f32,
+    pub material: usize
 }
 
 impl Sphere {
     pub fn new(center: Point, radius: f32) -> Sphere {
-        Sphere { center, radius }
+        Sphere { center, radius, material: 0 }
+    }
+
+    pub fn with_material(self, material: usize) -> Sphere {
+        Sphere { material, ..self }
     }
 
     pub fn intersect(self, ray: Ray) -> Option<Intersection> {
@@ -25,7 +30,8 @@ impl Sphere {
                 Some(Intersection {
                     t,
                     point: ray.at(t),
-                    object: self.into()
+                    object: self.into(),
+                    barycentric: (0., 0.)
                 })
             } else {
                 let t = (-half_b + root) / a;
@@ -33,7 +39,8 @@ impl Sphere {
                     Some(Intersection {
                         t,
                         point: ray.at(t),
-                        object: self.into()
+                        object: self.into(),
+                        barycentric: (0., 0.)
                     })
                 } else {
                     None
@@ -48,11 +55,15 @@ impl Sphere {
         (point - self.center).normalize()
     }
 
-    pub fn apply_transform(self, transform: &Matrix) -> Sphere {
-        Sphere {
-            center: (&transform.multiply(&self.center.into())).into(),
-            radius: self.radius
-        }
+    /// Moves the center and scales the radius. Returns `None` if the transform
+    /// scales unevenly, since that would turn the sphere into an ellipsoid.
+    pub fn apply_transform(self, transform: &Matrix) -> Option<Sphere> {
+        let scale = transform.uniform_scale()?;
+        Some(Sphere {
+            center: transform.transform_point(self.center),
+            radius: self.radius * scale,
+            material: self.material
+        })
     }
 }
 
@@ -107,4 +118,22 @@ mod tests {
         let result  = sphere.normal_at_point(point);
         assert_eq!(Vector::new(0., 0., 1.), result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_apply_transform() {
+        let sphere = Sphere::new(Point::new(1., 0., 0.), 2.)
+            .apply_transform(&Matrix::translate(0., 3., 0.).multiply(&Matrix::scale(0.5, 0.5, 0.5)))
+            .unwrap();
+        assert_eq!(Point::new(0.5, 3., 0.), sphere.center);
+        assert!((sphere.radius - 1.).abs() < 1e-6);
+
+        let rotated = Sphere::new(Point::new(1., 0., 0.), 2.).apply_transform(&Matrix::rotate_z(std::f32::consts::FRAC_PI_2)).unwrap();
+        assert!((rotated.center - Point::new(0., 1., 0.)).len() < 1e-6);
+        assert!((rotated.radius - 2.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_apply_non_uniform_scale() {
+        assert!(Sphere::new(Point::new(0., 0., 0.), 1.).apply_transform(&Matrix::scale(1., 2., 1.)).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+//! STL parser for both the ASCII and the binary variant.
+
+use std::{fmt, fs, io, path::{Path, PathBuf}};
+
+use crate::{point::Point, vector::Vector, mesh::{Mesh, Face}, EPSILON};
+
+const HEADER_SIZE: usize = 84;
+const FACET_SIZE: usize = 50;
+
+#[derive(Debug)]
+pub enum StlError {
+    Io(PathBuf, io::Error),
+    /// The file ends in the middle of a facet or before all the facets its
+    /// header announces; `expected` is only known for binary files.
+    Truncated { file: PathBuf, triangles: usize, expected: Option<usize> },
+    Parse { file: PathBuf, line: usize, message: String }
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StlError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            StlError::Truncated { file, triangles, expected: Some(expected) } =>
+                write!(f, "{}: truncated after {} of {} triangles", file.display(), triangles, expected),
+            StlError::Truncated { file, triangles, expected: None } =>
+                write!(f, "{}: truncated after {} triangles", file.display(), triangles),
+            StlError::Parse { file, line, message } => write!(f, "{}:{}: {}", file.display(), line, message)
+        }
+    }
+}
+
+impl std::error::Error for StlError {}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, StlError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|error| StlError::Io(path.to_path_buf(), error))?;
+    parse(&bytes, path)
+}
+
+/// Parses STL data of either variant; `file` is only used in error messages.
+pub fn parse(bytes: &[u8], file: &Path) -> Result<Mesh, StlError> {
+    let binary_size = binary_triangle_count(bytes).map(|count| HEADER_SIZE + count * FACET_SIZE);
+    let fits_binary = binary_size.is_some_and(|size| bytes.len() >= size);
+    let keyword = |keyword: &[u8]| bytes.windows(keyword.len()).any(|window| window == keyword);
+    // Binary files may also start with `solid`, so the header's facet count decides.
+    let looks_ascii = bytes.trim_ascii_start().starts_with(b"solid") && (keyword(b"facet") || keyword(b"endsolid"));
+    if looks_ascii && !fits_binary {
+        let source = String::from_utf8_lossy(bytes);
+        parse_ascii(&source, file)
+    } else {
+        parse_binary(bytes, file)
+    }
+}
+
+fn binary_triangle_count(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(80..HEADER_SIZE)?;
+    Some(u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize)
+}
+
+/// Appends a facet to `mesh`. Degenerate facets keep a zero normal; they
+/// are never hit.
+fn facet(mesh: &mut Mesh, normal: Vector, vertices: [Point; 3]) {
+    let [v0, v1, v2] = vertices;
+    let normal = if normal.len() > EPSILON {
+        normal
+    } else {
+        (v1 - v0).cross(v2 - v0)
+    };
+    let normal = if normal.len() > EPSILON { normal.normalize() } else { normal };
+    let first = mesh.positions.len() as u32;
+    mesh.positions.extend(vertices);
+    mesh.normals.extend([normal; 3]);
+    mesh.faces.push(Face::new([first, first + 1, first + 2]));
+}
+
+pub fn parse_binary(bytes: &[u8], file: &Path) -> Result<Mesh, StlError> {
+    let expected = match binary_triangle_count(bytes) {
+        Some(count) => count,
+        None => return Err(StlError::Truncated { file: file.to_path_buf(), triangles: 0, expected: None })
+    };
+    let available = (bytes.len() - HEADER_SIZE) / FACET_SIZE;
+    if available < expected {
+        return Err(StlError::Truncated { file: file.to_path_buf(), triangles: available, expected: Some(expected) });
+    }
+
+    let float = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+    let vector = |offset: usize| Vector::new(float(offset), float(offset + 4), float(offset + 8));
+    let point = |offset: usize| Point::new(float(offset), float(offset + 4), float(offset + 8));
+
+    let mut mesh = Mesh::new(Vec::with_capacity(3 * expected), Vec::with_capacity(expected));
+    for index in 0..expected {
+        let offset = HEADER_SIZE + index * FACET_SIZE;
+        facet(&mut mesh, vector(offset), [point(offset + 12), point(offset + 24), point(offset + 36)]);
+    }
+    Ok(mesh)
+}
+
+pub fn parse_ascii(source: &str, file: &Path) -> Result<Mesh, StlError> {
+    let mut mesh = Mesh::new(vec![], vec![]);
+    let mut normal = None;
+    let mut vertices = vec![];
+    let mut in_loop = false;
+    let mut in_solid = false;
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: String| StlError::Parse { file: file.to_path_buf(), line: index + 1, message };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let keyword = match tokens.first() {
+            Some(keyword) => *keyword,
+            None => continue
+        };
+        let triple = |start: usize| -> Result<[f32; 3], StlError> {
+            if tokens.len() != start + 3 {
+                return Err(error(format!("expected 3 numbers after '{}'", tokens[..start].join(" "))));
+            }
+            let mut values = [0.; 3];
+            for (value, token) in values.iter_mut().zip(&tokens[start..]) {
+                *value = token.parse().map_err(|_| error(format!("invalid number '{}'", token)))?;
+            }
+            Ok(values)
+        };
+
+        match (keyword, in_solid, normal.is_some(), in_loop) {
+            ("solid", false, _, _) => in_solid = true,
+            ("endsolid", true, false, _) => in_solid = false,
+            ("facet", true, false, _) => {
+                if tokens.get(1) != Some(&"normal") {
+                    return Err(error("expected 'facet normal'".to_string()));
+                }
+                let [x, y, z] = triple(2)?;
+                normal = Some(Vector::new(x, y, z));
+            },
+            ("outer", true, true, false) if tokens[1..] == ["loop"] => in_loop = true,
+            ("vertex", true, true, true) => {
+                if vertices.len() == 3 {
+                    return Err(error("only triangular facets are supported".to_string()));
+                }
+                let [x, y, z] = triple(1)?;
+                vertices.push(Point::new(x, y, z));
+            },
+            ("endloop", true, true, true) => {
+                if vertices.len() != 3 {
+                    return Err(error(format!("facet has {} vertices, expected 3", vertices.len())));
+                }
+                in_loop = false;
+            },
+            ("endfacet", true, true, false) => {
+                let corners: [Point; 3] = match vertices.as_slice().try_into() {
+                    Ok(corners) => corners,
+                    Err(_) if vertices.is_empty() => return Err(error("facet has no vertex loop".to_string())),
+                    Err(_) => return Err(error(format!("facet has {} vertices, expected 3", vertices.len())))
+                };
+                if let Some(normal) = normal.take() {
+                    facet(&mut mesh, normal, corners);
+                }
+                vertices.clear();
+            },
+            _ => return Err(error(format!("unexpected '{}'", line.trim())))
+        }
+    }
+
+    if in_solid {
+        return Err(StlError::Truncated { file: file.to_path_buf(), triangles: mesh.faces.len(), expected: None });
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "solid cube corner
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 0 0 1
+      vertex 1 0 0
+    endloop
+  endfacet
+endsolid cube corner
+";
+
+    fn binary(header: &[u8], facets: &[([f32; 3], [[f32; 3]; 3])], count: u32) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(count.to_le_bytes());
+        for (normal, vertices) in facets {
+            for value in normal.iter().chain(vertices.iter().flatten()) {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mesh = parse(ASCII.as_bytes(), Path::new("test.stl")).unwrap();
+        assert_eq!(2, mesh.faces.len());
+        assert_eq!(Point::new(1., 0., 0.), mesh.triangle(0).v1);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), mesh.triangle(0).n1);
+        // Zero facet normals are recomputed from the winding.
+        assert_eq!(Some(Vector::new(0., 1., 0.)), mesh.triangle(1).n3);
+    }
+
+    #[test]
+    fn test_binary() {
+        let facets = [([0., 0., 2.], [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]])];
+        // Binary files starting with "solid" must still be read as binary.
+        let mesh = parse(&binary(b"solid exported by a CAD tool", &facets, 1), Path::new("test.stl")).unwrap();
+        assert_eq!(1, mesh.faces.len());
+        assert_eq!(Point::new(0., 1., 0.), mesh.triangle(0).v2);
+        assert_eq!(Some(Vector::new(0., 0., 1.)), mesh.triangle(0).n2);
+
+        // Nor do bytes padding the file after the last facet make it ASCII.
+        let mut padded = binary(b"solid padded", &facets, 1);
+        padded.extend(b"\0\0\0\0 endsolid padded\n");
+        let mesh = parse(&padded, Path::new("test.stl")).unwrap();
+        assert_eq!(1, mesh.faces.len());
+        assert_eq!(Point::new(0., 1., 0.), mesh.triangle(0).v2);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let facets = [([0., 0., 1.], [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]])];
+        let message = |bytes: &[u8]| parse(bytes, Path::new("test.stl")).unwrap_err().to_string();
+        assert_eq!("test.stl: truncated after 1 of 3 triangles", message(&binary(b"", &facets, 3)));
+        assert_eq!("test.stl: truncated after 0 triangles", message(&[0; 20]));
+        assert_eq!("test.stl: truncated after 1 triangles", message(&ASCII.as_bytes()[..ASCII.find("  facet normal 0 0 0").unwrap()]));
+    }
+
+    #[test]
+    fn test_ascii_errors() {
+        let message = |source: &str| parse_ascii(source, Path::new("test.stl")).unwrap_err().to_string();
+        assert_eq!("test.stl:2: invalid number 'x'", message("solid\nfacet normal x 0 0"));
+        assert_eq!("test.stl:2: unexpected 'vertex 0 0 0'", message("solid\nvertex 0 0 0"));
+        assert_eq!("test.stl:6: facet has 2 vertices, expected 3", message("solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop"));
+        assert_eq!("test.stl:3: facet has no vertex loop", message("solid x\nfacet normal 0 0 1\nendfacet\nendsolid x"));
+    }
+}
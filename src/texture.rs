@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use image::{ImageResult, RgbImage};
+
+use crate::color::Color;
+
+/// How texel coordinates outside the image are brought back onto it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    /// Coordinates stick to the edge texels.
+    Clamp,
+    /// Every other repetition is mirrored.
+    Mirror
+}
+
+impl Wrap {
+    fn apply(self, index: i64, size: u32) -> usize {
+        let size = size as i64;
+        let index = match self {
+            Wrap::Repeat => index.rem_euclid(size),
+            Wrap::Clamp => index.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let index = index.rem_euclid(2 * size);
+                if index < size { index } else { 2 * size - 1 - index }
+            }
+        };
+        index as usize
+    }
+}
+
+/// Image sampled with wrapping texture coordinates. Texels are stored as
+/// linear colors; `(0, 0)` is the bottom-left corner as in OBJ and MTL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+    texels: Vec<Color>
+}
+
+impl Texture {
+    pub fn new(width: u32, height: u32, texels: Vec<Color>) -> Texture {
+        assert_eq!((width * height) as usize, texels.len(), "texture size does not match its texels");
+        Texture { width, height, wrap_u: Wrap::Repeat, wrap_v: Wrap::Repeat, texels }
+    }
+
+    pub fn with_wrap(self, wrap_u: Wrap, wrap_v: Wrap) -> Texture {
+        Texture { wrap_u, wrap_v, ..self }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Texture> {
+        Ok(Texture::from_image(&image::open(path)?.to_rgb8()))
+    }
+
+    pub fn from_image(image: &RgbImage) -> Texture {
+        let texels = image.pixels().map(|pixel| Color::from_srgb8(pixel[0], pixel[1], pixel[2])).collect();
+        Texture::new(image.width(), image.height(), texels)
+    }
+
+    /// Loads an image that holds data rather than colors, such as a bump
+    /// map, keeping its values as stored instead of decoding them from sRGB.
+    pub fn load_linear<P: AsRef<Path>>(path: P) -> ImageResult<Texture> {
+        Ok(Texture::from_image_linear(&image::open(path)?.to_rgb8()))
+    }
+
+    pub fn from_image_linear(image: &RgbImage) -> Texture {
+        let channel = |value: u8| value as f32 / 255.;
+        let texels = image.pixels().map(|pixel| Color::new(channel(pixel[0]), channel(pixel[1]), channel(pixel[2]))).collect();
+        Texture::new(image.width(), image.height(), texels)
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap_u.apply(x, self.width);
+        let y = self.wrap_v.apply(y, self.height);
+        self.texels[y * self.width as usize + x]
+    }
+
+    /// Bilinearly filtered color at `(u, v)`; coordinates outside `[0, 1]`
+    /// follow `wrap_u` and `wrap_v`.
+    pub fn sample(&self, u: f32, v: f32) -> Color {
+        let x = u * self.width as f32 - 0.5;
+        let y = (1. - v) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        self.texel(x0, y0) * ((1. - fx) * (1. - fy))
+            + self.texel(x0 + 1, y0) * (fx * (1. - fy))
+            + self.texel(x0, y0 + 1) * ((1. - fx) * fy)
+            + self.texel(x0 + 1, y0 + 1) * (fx * fy)
+    }
+
+    /// Derivatives of the luminance with respect to `u` and `v`, used to
+    /// treat the texture as a height field for bump mapping.
+    pub fn height_gradient(&self, u: f32, v: f32) -> (f32, f32) {
+        let du = 1. / self.width as f32;
+        let dv = 1. / self.height as f32;
+        let height = |u, v| self.sample(u, v).luminance();
+        (
+            (height(u + du, v) - height(u - du, v)) / (2. * du),
+            (height(u, v + dv) - height(u, v - dv)) / (2. * dv)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> Texture {
+        // Top row black-white, bottom row white-black.
+        Texture::new(2, 2, vec![Color::black(), Color::white(), Color::white(), Color::black()])
+    }
+
+    #[test]
+    fn test_sample() {
+        let texture = checker();
+        assert_eq!(Color::white(), texture.sample(0.25, 0.25));
+        assert_eq!(Color::black(), texture.sample(0.75, 0.25));
+        assert_eq!(Color::black(), texture.sample(0.25, 0.75));
+        assert_eq!(Color::white(), texture.sample(1.25, -0.75));
+        assert_eq!(Color::new(0.5, 0.5, 0.5), texture.sample(0.5, 0.25));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let ramp = Texture::new(4, 1, (0..4).map(|x| Color::white() * x as f32).collect());
+        assert_eq!(Color::white() * 2., ramp.sample(1.625, 0.5));
+        let clamped = ramp.clone().with_wrap(Wrap::Clamp, Wrap::Repeat);
+        assert_eq!(Color::white() * 3., clamped.sample(1.625, 0.5));
+        assert_eq!(Color::black(), clamped.sample(-0.375, 0.5));
+        let mirrored = ramp.with_wrap(Wrap::Mirror, Wrap::Repeat);
+        assert_eq!(Color::white() * 1., mirrored.sample(1.625, 0.5));
+        assert_eq!(Color::white() * 1., mirrored.sample(-0.375, 0.5));
+    }
+
+    #[test]
+    fn test_height_gradient() {
+        let ramp = Texture::new(4, 1, (0..4).map(|x| Color::white() * (x as f32 / 4.)).collect());
+        let (du, dv) = ramp.height_gradient(0.5, 0.5);
+        assert!((du - 1.).abs() < 1e-4);
+        assert_eq!(0., dv);
+    }
+
+    #[test]
+    fn test_from_image() {
+        let image = RgbImage::from_fn(3, 1, |x, _| image::Rgb([x as u8 * 100, 0, 0]));
+        let texture = Texture::from_image(&image);
+        assert_eq!((3, 1), (texture.width, texture.height));
+        assert_eq!(Color::from_srgb8(100, 0, 0), texture.sample(0.5, 0.5));
+        assert_eq!(Color::new(100. / 255., 0., 0.), Texture::from_image_linear(&image).sample(0.5, 0.5));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{point::Point, intersection::Intersection, ray::Ray, vector::Vector, matrix::Matrix, EPSILON, aabb::{Bounded, AABB}};
+use crate::{color::Color, point::Point, intersection::Intersection, ray::Ray, vector::Vector, matrix::Matrix, EPSILON, aabb::{Bounded, AABB}};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Triangle {
@@ -8,15 +8,36 @@ pub struct Triangle {
     pub n1: Option<Vector>,
     pub n2: Option<Vector>,
     pub n3: Option<Vector>,
+    /// Texture coordinates of `v0`, `v1` and `v2`.
+    pub uv1: Option<(f32, f32)>,
+    pub uv2: Option<(f32, f32)>,
+    pub uv3: Option<(f32, f32)>,
+    /// Vertex colors of `v0`, `v1` and `v2`, multiplied into the albedo.
+    pub color1: Option<Color>,
+    pub color2: Option<Color>,
+    pub color3: Option<Color>,
+    pub material: usize,
 }
 
 impl Triangle {
     pub fn new(v0: Point, v1: Point, v2: Point) -> Triangle {
-        Triangle { v0, v1, v2, n1: None, n2: None, n3: None }
+        Triangle { v0, v1, v2, n1: None, n2: None, n3: None, uv1: None, uv2: None, uv3: None, color1: None, color2: None, color3: None, material: 0 }
     }
 
     pub fn with_normals(v0: Point, v1: Point, v2: Point, n1: Vector, n2: Vector, n3: Vector) -> Triangle {
-        Triangle { v0, v1, v2, n1: Some(n1.normalize()), n2: Some(n2.normalize()), n3: Some(n3.normalize()) }
+        Triangle { v0, v1, v2, n1: Some(n1.normalize()), n2: Some(n2.normalize()), n3: Some(n3.normalize()), uv1: None, uv2: None, uv3: None, color1: None, color2: None, color3: None, material: 0 }
+    }
+
+    pub fn with_uvs(self, uv1: (f32, f32), uv2: (f32, f32), uv3: (f32, f32)) -> Triangle {
+        Triangle { uv1: Some(uv1), uv2: Some(uv2), uv3: Some(uv3), ..self }
+    }
+
+    pub fn with_colors(self, color1: Color, color2: Color, color3: Color) -> Triangle {
+        Triangle { color1: Some(color1), color2: Some(color2), color3: Some(color3), ..self }
+    }
+
+    pub fn with_material(self, material: usize) -> Triangle {
+        Triangle { material, ..self }
     }
 
     pub fn intersect(self, ray: Ray) -> Option<Intersection> {
@@ -32,7 +53,7 @@ impl Triangle {
         let inv_det = 1.0 / det;
         let t = ray.origin - self.v0;
         let u = t.dot(p) * inv_det;
-        if u < 0. || u > 1. {
+        if !(0. ..=1.).contains(&u) {
             return None;
         }
 
@@ -49,40 +70,87 @@ impl Triangle {
                 t,
                 object: self.into(),
                 point: ray.at(t),
+                barycentric: (u, v),
             });
         }
 
         None
     }
 
+    /// Shading normal at `point`, which should lie in the plane of the triangle.
     pub fn normal_at_point(self, point: Point) -> Vector {
-        if let Some(n1) = self.n1 {
-            if let Some(n2) = self.n2 {
-                if let Some(n3) = self.n3 {
-                    let v0 = self.v0 - point;
-                    let v1 = self.v1 - point;
-                    let v2 = self.v2 - point;
-                    let u = v1.cross(v2).dot(v0) / n1.dot(n1);
-                    let v = v2.cross(v0).dot(v1) / n2.dot(n2);
-                    let w = 1.0 - u - v;
-                    return n1 * u + n2 * v + n3 * w;
-                }
+        let (_, u, v) = self.barycentric(point);
+        self.normal_at(u, v)
+    }
+
+    /// Shading normal at barycentric coordinates `(u, v)`: the normalized
+    /// interpolation of the vertex normals, or the face normal without them.
+    pub fn normal_at(self, u: f32, v: f32) -> Vector {
+        if let (Some(n1), Some(n2), Some(n3)) = (self.n1, self.n2, self.n3) {
+            let normal = n1 * (1. - u - v) + n2 * u + n3 * v;
+            if normal.len() > EPSILON {
+                return normal.normalize();
             }
         }
-        
+
         let e1 = self.v1 - self.v0;
         let e2 = self.v2 - self.v0;
         e1.cross(e2).normalize()
     }
 
+    /// Barycentric weights of `v0`, `v1` and `v2` for a point in the plane of the triangle.
+    pub fn barycentric(self, point: Point) -> (f32, f32, f32) {
+        let e1 = self.v1 - self.v0;
+        let e2 = self.v2 - self.v0;
+        let p = point - self.v0;
+        let d11 = e1.dot(e1);
+        let d12 = e1.dot(e2);
+        let d22 = e2.dot(e2);
+        let denominator = d11 * d22 - d12 * d12;
+        let v = (d22 * p.dot(e1) - d12 * p.dot(e2)) / denominator;
+        let w = (d11 * p.dot(e2) - d12 * p.dot(e1)) / denominator;
+        (1. - v - w, v, w)
+    }
+
+    /// Texture coordinates at barycentric coordinates `(u, v)`.
+    pub fn uv_at(self, u: f32, v: f32) -> Option<(f32, f32)> {
+        let (uv1, uv2, uv3) = (self.uv1?, self.uv2?, self.uv3?);
+        let w = 1. - u - v;
+        Some((w * uv1.0 + u * uv2.0 + v * uv3.0, w * uv1.1 + u * uv2.1 + v * uv3.1))
+    }
+
+    /// Vertex color at barycentric coordinates `(u, v)`.
+    pub fn color_at(self, u: f32, v: f32) -> Option<Color> {
+        let (color1, color2, color3) = (self.color1?, self.color2?, self.color3?);
+        Some(color1 * (1. - u - v) + color2 * u + color3 * v)
+    }
+
+    /// Directions in which the surface moves as `u` and `v` increase, or
+    /// `None` without texture coordinates or when they are degenerate.
+    pub fn uv_tangents(self) -> Option<(Vector, Vector)> {
+        let (uv1, uv2, uv3) = (self.uv1?, self.uv2?, self.uv3?);
+        let (du1, dv1) = (uv2.0 - uv1.0, uv2.1 - uv1.1);
+        let (du2, dv2) = (uv3.0 - uv1.0, uv3.1 - uv1.1);
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() < EPSILON {
+            return None;
+        }
+        let e1 = self.v1 - self.v0;
+        let e2 = self.v2 - self.v0;
+        Some(((e1 * dv2 - e2 * dv1) / determinant, (e2 * du1 - e1 * du2) / determinant))
+    }
+
     pub fn apply_transform(self, transform: &Matrix) -> Triangle {
+        let normal_matrix = transform.normal_matrix();
+        let normal = |n: Vector| normal_matrix.transform_vector(n).normalize();
         Triangle {
-            v0: (&transform.multiply(&self.v0.into())).into(),
-            v1: (&transform.multiply(&self.v1.into())).into(),
-            v2: (&transform.multiply(&self.v2.into())).into(),
-            n1: self.n1.map(|n| Vector::from(&transform.multiply(&n.into()))),
-            n2: self.n2.map(|n| Vector::from(&transform.multiply(&n.into()))),
-            n3: self.n3.map(|n| Vector::from(&transform.multiply(&n.into())))
+            v0: transform.transform_point(self.v0),
+            v1: transform.transform_point(self.v1),
+            v2: transform.transform_point(self.v2),
+            n1: self.n1.map(normal),
+            n2: self.n2.map(normal),
+            n3: self.n3.map(normal),
+            ..self
         }
     }
 }
@@ -148,6 +216,94 @@ mod tests {
         let triangle = Triangle::new(v0, v1, v2);
         let point = Point::new(5., 5., 9.);
         let result = triangle.normal_at_point(point);
-        assert_eq!(Vector::new(0., 0., 1.), result);
+        assert_eq!(Vector::new(0., 0., -1.), result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_uvs() {
+        let triangle = Triangle::new(Point::new(0., 0., 0.), Point::new(2., 0., 0.), Point::new(0., 4., 0.))
+            .with_uvs((0., 0.), (1., 0.), (0., 1.));
+        assert_eq!((0.5, 0.25, 0.25), triangle.barycentric(Point::new(0.5, 1., 0.)));
+        assert_eq!(Some((0.25, 0.25)), triangle.uv_at(0.25, 0.25));
+        assert_eq!(Some((Vector::new(2., 0., 0.), Vector::new(0., 4., 0.))), triangle.uv_tangents());
+        assert_eq!(None, Triangle::new(triangle.v0, triangle.v1, triangle.v2).uv_tangents());
+    }
+
+    #[test]
+    fn test_color_at() {
+        let triangle = Triangle::new(Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.));
+        assert_eq!(None, triangle.color_at(0.25, 0.25));
+        let colored = triangle.with_colors(Color::new(1., 0., 0.), Color::new(0., 1., 0.), Color::new(0., 0., 1.));
+        assert_eq!(Some(Color::new(0.5, 0.25, 0.25)), colored.color_at(0.25, 0.25));
+        assert_eq!(Some(Color::new(0., 1., 0.)), colored.color_at(1., 0.));
+    }
+
+    #[test]
+    fn test_intersect_barycentric() {
+        let triangle = Triangle::new(Point::new(0., 0., 0.), Point::new(2., 0., 0.), Point::new(0., 4., 0.));
+        let intersection = triangle.intersect(Ray::new(Point::new(0.5, 1., 3.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!((0.25, 0.25), intersection.barycentric);
+        let (u, v) = intersection.barycentric;
+        assert_eq!(intersection.point, triangle.v0 + (triangle.v1 - triangle.v0) * u + (triangle.v2 - triangle.v0) * v);
+
+        // Hits on the vertices and edges give the corresponding extreme coordinates.
+        let at_v2 = triangle.intersect(Ray::new(Point::new(0., 4., 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!((0., 1.), at_v2.barycentric);
+        let on_edge = triangle.intersect(Ray::new(Point::new(1., 0., 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!((0.5, 0.), on_edge.barycentric);
+    }
+
+    #[test]
+    fn test_normal_at() {
+        let triangle = Triangle::with_normals(
+            Point::new(0., 0., 0.), Point::new(1., 0., 0.), Point::new(0., 1., 0.),
+            Vector::new(0., 0., 1.), Vector::new(1., 0., 1.), Vector::new(0., 1., 1.)
+        );
+        assert_eq!(Vector::new(0., 0., 1.), triangle.normal_at(0., 0.));
+        assert!((triangle.normal_at(1., 0.) - Vector::new(1., 0., 1.).normalize()).len() < 1e-6);
+
+        // Halfway along the edge from v1 to v2 the normal is the normalized average of theirs.
+        let halfway = triangle.normal_at(0.5, 0.5);
+        let expected = (Vector::new(1., 0., 1.).normalize() + Vector::new(0., 1., 1.).normalize()).normalize();
+        assert!((halfway - expected).len() < 1e-6);
+        assert!((halfway.len() - 1.).abs() < 1e-6);
+        assert!((triangle.normal_at_point(Point::new(0.5, 0.5, 0.)) - expected).len() < 1e-6);
+
+        // The centroid weighs all three equally.
+        let centroid = triangle.normal_at(1. / 3., 1. / 3.);
+        let sum = Vector::new(0., 0., 1.) + Vector::new(1., 0., 1.).normalize() + Vector::new(0., 1., 1.).normalize();
+        assert!((centroid - sum.normalize()).len() < 1e-6);
+    }
+
+    #[test]
+    fn test_intersection_attributes() {
+        let triangle = Triangle::with_normals(
+            Point::new(0., 0., 0.), Point::new(4., 0., 0.), Point::new(0., 4., 0.),
+            Vector::new(0., 0., 1.), Vector::new(1., 0., 0.), Vector::new(0., 1., 0.)
+        )
+            .with_uvs((0., 0.), (1., 0.), (0., 2.))
+            .with_colors(Color::new(1., 0., 0.), Color::new(0., 1., 0.), Color::new(0., 0., 1.));
+        let intersection = triangle.intersect(Ray::new(Point::new(1., 2., 1.), Vector::new(0., 0., -1.))).unwrap();
+        assert_eq!((0.25, 0.5), intersection.barycentric);
+        assert_eq!(Some((0.25, 1.)), intersection.uv());
+        assert_eq!(Some(Color::new(0.25, 0.25, 0.5)), intersection.color());
+        assert!((intersection.normal() - Vector::new(0.25, 0.5, 0.25).normalize()).len() < 1e-6);
+    }
+
+    #[test]
+    fn test_apply_transform() {
+        let normal = Vector::new(1., 1., 0.).normalize();
+        let triangle = Triangle::with_normals(
+            Point::new(1., 0., 0.), Point::new(0., 1., 0.), Point::new(0., 1., 1.),
+            normal, normal, normal
+        );
+        let transformed = triangle.apply_transform(&Matrix::translate(0., 0., 1.).multiply(&Matrix::scale(2., 1., 1.)));
+        assert_eq!(Point::new(2., 0., 1.), transformed.v0);
+        assert_eq!(Point::new(0., 1., 2.), transformed.v2);
+        // Interpolated normals stay perpendicular to the stretched face.
+        let face_normal = (transformed.v1 - transformed.v0).cross(transformed.v2 - transformed.v0).normalize();
+        let interpolated = transformed.normal_at(0.2, 0.3);
+        assert!((interpolated - face_normal).len() < 1e-6 || (interpolated + face_normal).len() < 1e-6);
+        assert!((interpolated.len() - 1.).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, Sub, SubAssign, Mul, MulAssign, Div, DivAssign, Neg};
+use std::ops::{Add, AddAssign, Sub, SubAssign, Mul, MulAssign, Div, DivAssign, Neg, Index};
 
 use crate::{matrix::Matrix, m};
 
@@ -38,8 +38,27 @@ impl Vector {
         self / self.len()
     }
 
+    /// Transforms the vector as a direction, so translations do not affect it.
     pub fn apply_transform(self, transform: &Matrix) -> Vector {
-        (&transform.multiply(&self.into())).into()
+        transform.transform_vector(self)
+    }
+
+    /// Mirrors the vector about `normal`, which must be normalized.
+    pub fn reflect(self, normal: Vector) -> Vector {
+        self - normal * (2. * self.dot(normal))
+    }
+
+    /// Bends a normalized direction through a surface with relative index of
+    /// refraction `eta`, or returns `None` on total internal reflection.
+    /// `normal` must point against the incoming direction.
+    pub fn refract(self, normal: Vector, eta: f32) -> Option<Vector> {
+        let cos_i = -self.dot(normal);
+        let sin2_t = eta * eta * (1. - cos_i * cos_i);
+        if sin2_t > 1. {
+            return None;
+        }
+        let cos_t = (1. - sin2_t).sqrt();
+        Some(self * eta + normal * (eta * cos_i - cos_t))
     }
 }
 
@@ -123,6 +142,19 @@ impl Neg for Vector {
     }
 }
 
+impl Index<usize> for Vector {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Index out of bounds")
+        }
+    }
+}
+
 impl From<&Matrix> for Vector {
     fn from(matrix: &Matrix) -> Vector {
         if matrix.rows == 4 {
@@ -133,13 +165,13 @@ impl From<&Matrix> for Vector {
     }
 }
 
-impl Into<Matrix> for Vector {
-    fn into(self) -> Matrix {
+impl From<Vector> for Matrix {
+    fn from(v: Vector) -> Matrix {
         m! [
-            self.x;
-            self.y;
-            self.z;
-            1.
+            v.x;
+            v.y;
+            v.z;
+            0.
         ]
     }
 }
@@ -235,6 +267,12 @@ mod tests {
         assert_eq!(5., b.z);
     }
 
+    #[test]
+    fn test_apply_transform() {
+        let transform = Matrix::translate(5., 5., 5.).multiply(&Matrix::scale(2., 1., 1.));
+        assert_eq!(Vector::new(2., 2., 3.), Vector::new(1., 2., 3.).apply_transform(&transform));
+    }
+
     #[test]
     fn test_add() {
         let vector = Vector::new(5., 4., 3.);
@@ -311,6 +349,25 @@ mod tests {
         assert_eq!(Vector::new(2.5, 2., 1.5), vector);
     }  
 
+    #[test]
+    fn test_reflect() {
+        let vector = Vector::new(1., -1., 0.);
+        let normal = Vector::new(0., 1., 0.);
+        assert_eq!(Vector::new(1., 1., 0.), vector.reflect(normal));
+    }
+
+    #[test]
+    fn test_refract() {
+        let normal = Vector::new(0., 1., 0.);
+        let straight = Vector::new(0., -1., 0.);
+        assert_eq!(Some(straight), straight.refract(normal, 1.5));
+        let grazing = Vector::new(1., -1., 0.).normalize();
+        let refracted = grazing.refract(normal, 1. / 1.5).unwrap();
+        assert!((refracted.x - grazing.x / 1.5).abs() < 1e-6);
+        assert!((refracted.len() - 1.).abs() < 1e-6);
+        assert_eq!(None, grazing.refract(normal, 1.5));
+    }
+
     #[test]
     fn test_neg() {
         let mut vector = Vector::new(5., 4., 3.);